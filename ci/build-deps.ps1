@@ -63,6 +63,34 @@ function Initialize-Filesystem
     New-Item -ItemType Directory .\publish
 }
 
+function Assert-UpdateKeys
+{
+    Write-BeginStep $MYINVOCATION
+
+    # Published binaries need the public key to verify updates, and need to be signed so `squiflog update` installs them
+    # These are set in the project's environment, with the signing key kept secret
+    if ($IsPublishedBuild -and (-not $env:SQUIFLOG_UPDATE_PUBLIC_KEY -or -not $env:SQUIFLOG_UPDATE_SIGNING_KEY)) {
+        throw [System.Exception] "SQUIFLOG_UPDATE_PUBLIC_KEY and SQUIFLOG_UPDATE_SIGNING_KEY must be set to publish"
+    }
+}
+
+function Publish-Binary($target, $version)
+{
+    Write-BeginStep $MYINVOCATION
+
+    $suffix = @{ $true = ".exe"; $false = "" }[$target -like "*-windows-*"]
+    $artifact = "./publish/squiflog-$target$suffix"
+
+    Copy-Item "./target/$target/release/squiflog$suffix" $artifact
+
+    # The signature covers the version and target, so it can't be reused for another release
+    if ($env:SQUIFLOG_UPDATE_SIGNING_KEY) {
+        Run-Command -Exe cargo -ArgumentList 'run', '-p squiflog', '--example sign-release', '--', $artifact, $version, $target
+    } else {
+        Write-Output "Not signing $artifact"
+    }
+}
+
 function Invoke-LinuxBuild
 {
     Write-BeginStep $MYINVOCATION
@@ -74,6 +102,12 @@ function Invoke-LinuxTests
     Write-BeginStep $MYINVOCATION
 
     Run-Command -Exe cargo -ArgumentList 'test', '--target x86_64-unknown-linux-musl'
+
+    # The core parser must keep building without `std`, and for wasm
+    # A target without `std` catches dependencies that quietly pull it in
+    Run-Command -Exe rustup -ArgumentList 'target', 'add', 'thumbv7em-none-eabi'
+    Run-Command -Exe cargo -ArgumentList 'build', '-p squiflog-core', '--no-default-features', '--target thumbv7em-none-eabi'
+    Run-Command -Exe cargo -ArgumentList 'build', '-p squiflog-wasm', '--target wasm32-unknown-unknown'
 }
 
 function Invoke-DockerBuild
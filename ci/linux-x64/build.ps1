@@ -7,6 +7,9 @@ Push-Location "$PSScriptRoot/../../"
 
 . "./ci/build-deps.ps1"
 
+# Reported by `squiflog update` when checking for newer releases
+$env:SQUIFLOG_VERSION = Get-SemVer $shortver
+
 function Invoke-SmokeTest($protocol, $format) {
     Write-BeginStep $MYINVOCATION
 
@@ -42,8 +45,10 @@ function Invoke-SmokeTest($protocol, $format) {
 }
 
 Initialize-Filesystem
+Assert-UpdateKeys
 Invoke-LinuxBuild
 Invoke-LinuxTests
+Publish-Binary "x86_64-unknown-linux-musl" (Get-SemVer $shortver)
 Invoke-DockerBuild
 
 Build-TestAppContainer
@@ -7,9 +7,14 @@ Push-Location "$PSScriptRoot/../../"
 
 . "./ci/build-deps.ps1"
 
+# Reported by `squiflog update` when checking for newer releases
+$env:SQUIFLOG_VERSION = Get-SemVer $shortver
+
 Initialize-Filesystem
+Assert-UpdateKeys
 Invoke-WindowsBuild
 Invoke-WindowsTests
+Publish-Binary "x86_64-pc-windows-msvc" (Get-SemVer $shortver)
 Invoke-LinuxBuild
 Invoke-NuGetPack (Get-SemVer $shortver)
 
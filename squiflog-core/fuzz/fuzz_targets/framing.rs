@@ -0,0 +1,86 @@
+#![no_main]
+
+use bytes::{
+    Bytes,
+    BytesMut,
+};
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::{
+    Decoder,
+    Encoder,
+};
+
+use squiflog_core::{
+    codec::{
+        NewlineCodec,
+        OctetCountingCodec,
+        SyslogCodec,
+    },
+    Error,
+};
+
+// The first byte picks the codec and how the rest of the input is
+// split into chunks, to simulate reads from a socket
+fuzz_target!(|data: &[u8]| {
+    let (config, data) = match data.split_first() {
+        Some((config, data)) => (*config, data),
+        None => return,
+    };
+
+    let chunk_size = (config >> 2) as usize + 1;
+
+    match config & 0b11 {
+        0 => decode(OctetCountingCodec::with_max_length(256), data, chunk_size),
+        1 => decode(NewlineCodec::with_max_length(256), data, chunk_size),
+        _ => decode(SyslogCodec::with_max_length(256), data, chunk_size),
+    }
+
+    // Any frame that can be encoded must decode to the same bytes
+    let mut codec = SyslogCodec::new();
+    let mut buf = BytesMut::new();
+    if codec.encode(data, &mut buf).is_ok() {
+        let frame = codec
+            .decode_eof(&mut buf)
+            .expect("failed to decode an encoded frame")
+            .expect("missing an encoded frame")
+            .expect("an encoded frame is invalid");
+
+        assert_eq!(data, &*frame);
+        assert!(buf.is_empty());
+    }
+});
+
+fn decode(mut codec: impl Decoder<Item = Result<Bytes, Error>, Error = Error>, data: &[u8], chunk_size: usize) {
+    let mut buf = BytesMut::new();
+
+    for chunk in data.chunks(chunk_size) {
+        buf.extend_from_slice(chunk);
+
+        drain(&mut buf, |buf| codec.decode(buf));
+    }
+
+    drain(&mut buf, |buf| codec.decode_eof(buf));
+}
+
+// Invalid frames are items, so decoding itself never fails and continues until
+// there's no more input. An invalid frame may be returned before any of it is
+// consumed, but the next item must make progress, or decoding would never end
+fn drain(
+    buf: &mut BytesMut,
+    mut decode: impl FnMut(&mut BytesMut) -> Result<Option<Result<Bytes, Error>>, Error>,
+) {
+    let mut stalled = false;
+
+    loop {
+        let len = buf.len();
+
+        if decode(buf).expect("failed to decode").is_none() {
+            break;
+        }
+
+        let progressed = buf.len() < len;
+        assert!(progressed || !stalled, "decoding made no progress");
+
+        stalled = !progressed;
+    }
+}
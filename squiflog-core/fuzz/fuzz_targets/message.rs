@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use squiflog_core::syslog;
+
+fuzz_target!(|data: &[u8]| {
+    // Parsing arbitrary bytes always succeeds by falling back to RFC 3164
+    let msg = syslog::Message::from_bytes(data);
+
+    let clef = msg.into_owned().into_clef();
+    serde_json::to_vec(&clef).expect("failed to serialize CLEF");
+
+    let _ = syslog::Message::from_rfc5424_bytes(data);
+});
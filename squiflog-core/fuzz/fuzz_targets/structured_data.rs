@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use squiflog_core::syslog;
+
+// A valid RFC 5424 header, so the input is parsed as structured data and a message
+const HEADER: &[u8] = b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 ";
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(sd) = syslog::StructuredDataElement::from_bytes(data) {
+        assert!(!sd.id.is_empty());
+    }
+
+    let mut msg = HEADER.to_vec();
+    msg.extend_from_slice(data);
+
+    if let Ok(msg) = syslog::Message::from_rfc5424_bytes(&msg) {
+        let clef = msg.into_clef();
+        serde_json::to_vec(&clef).expect("failed to serialize CLEF");
+    }
+});
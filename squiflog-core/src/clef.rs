@@ -0,0 +1,507 @@
+use std::{borrow::Cow, collections::HashMap, str::FromStr};
+use serde_json::{json, Value};
+use chrono::{DateTime, Utc};
+
+use crate::{error::Error, syslog};
+
+/**
+A CLEF-encoded event.
+
+Serializing a message produces a JSON object with the reified `@t`, `@l`, `@m`,
+`@mt`, and `@x` properties alongside any additional properties.
+*/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Message<'a> {
+    #[serde(rename = "@t")]
+    pub timestamp: DateTime<Utc>,
+
+    #[serde(rename = "@l")]
+    pub level: Option<Cow<'a, str>>,
+
+    #[serde(rename = "@m")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<Cow<'a, str>>,
+
+    // @mt and @x are currently not used
+    #[serde(rename = "@mt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_template: Option<Cow<'a, str>>,
+
+    #[serde(rename = "@x")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exception: Option<Cow<'a, str>>,
+
+    // @i and @r are currently not implemented
+
+    // Everything else
+    #[serde(flatten)]
+    pub additional: HashMap<Cow<'a, str>, Value>,
+}
+
+/**
+Which fields are kept when the name of a SYSLOG header field conflicts
+with the id of a structured data element.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precedence {
+    /**
+    Fields from the SYSLOG header are preferred over structured data.
+    */
+    Header,
+    /**
+    Structured data is preferred over fields from the SYSLOG header.
+    */
+    #[default]
+    StructuredData,
+}
+
+impl FromStr for Precedence {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "header" => Ok(Precedence::Header),
+            "structureddata" | "structured_data" | "structured-data" => Ok(Precedence::StructuredData),
+            _ => Err(Error::msg(format!(
+                "invalid precedence `{}`, expected `Header` or `StructuredData`",
+                s
+            ))),
+        }
+    }
+}
+
+impl<'a> Message<'a> {
+    /**
+    Convert into a message that doesn't borrow from its input.
+    */
+    pub fn into_owned(self) -> Message<'static> {
+        Message {
+            timestamp: self.timestamp,
+            level: self.level.map(|v| Cow::Owned(v.into_owned())),
+            message: self.message.map(|v| Cow::Owned(v.into_owned())),
+            message_template: self.message_template.map(|v| Cow::Owned(v.into_owned())),
+            exception: self.exception.map(|v| Cow::Owned(v.into_owned())),
+            additional: self
+                .additional
+                .into_iter()
+                .map(|(k, v)| (Cow::Owned(k.into_owned()), v))
+                .collect(),
+        }
+    }
+
+    /**
+    Add a property to the message.
+
+    Names starting with `@` are reserved for reified CLEF properties, so they're
+    escaped with an additional leading `@`. If a property with the same name has
+    already been added then the new property is included with a
+    double-underscore-prefixed name instead, e.g.: "__host".
+    */
+    pub fn add_property(&mut self, name: impl Into<Cow<'a, str>>, value: Value) {
+        let mut name = name.into();
+
+        if name.starts_with('@') {
+            name = Cow::Owned(format!("@{}", name));
+        }
+
+        while self.additional.contains_key(&name) {
+            name = Cow::Owned(format!("__{}", name));
+        }
+
+        self.additional.insert(name, value);
+    }
+}
+
+impl<'a> syslog::Message<'a> {
+    /**
+    Covert a SYSLOG message into CLEF.
+
+    The contents of the SYSLOG message is inspected and deserialized as CLEF-encoded
+    JSON if possible. In this case, timestamp, message, and level information from
+    the embedded CLEF is given precedence over the SYSLOG header.
+
+    Structured data elements replace header fields with the same name.
+    Use [`into_clef_with`](#method.into_clef_with) to keep header fields instead.
+    */
+    pub fn into_clef(self) -> Message<'a> {
+        self.into_clef_with(Precedence::StructuredData)
+    }
+
+    /**
+    Covert a SYSLOG message into CLEF, using the given precedence to
+    resolve conflicts between the SYSLOG header and structured data.
+
+    The lower-priority field is replaced by the higher-priority one.
+    */
+    pub fn into_clef_with(self, precedence: Precedence) -> Message<'a> {
+        #![deny(unused_variables)]
+
+        let syslog::Message {
+            priority,
+            timestamp,
+            hostname,
+            app_name,
+            proc_id,
+            message_id,
+            structured_data,
+            message,
+        } = self;
+
+        let mut header = vec![(Cow::Borrowed("facility"), json!(priority.facility()))];
+        if let Some(hostname) = hostname {
+            header.push((Cow::Borrowed("hostname"), json!(hostname)));
+        }
+        if let Some(app_name) = app_name {
+            header.push((Cow::Borrowed("app_name"), json!(app_name)));
+        }
+        if let Some(proc_id) = proc_id {
+            header.push((Cow::Borrowed("proc_id"), json!(proc_id)));
+        }
+        if let Some(message_id) = message_id {
+            header.push((Cow::Borrowed("message_id"), json!(message_id)));
+        }
+
+        let mut sd = vec![];
+        for element in structured_data.into_iter().flatten() {
+            let mut params = vec![];
+            for (k, v) in element.params {
+                let mut map = HashMap::new();
+                map.insert(k, v);
+                params.push(map);
+            }
+            sd.push((element.id, json!(params)));
+        }
+
+        let mut clef = Message {
+            timestamp: timestamp.unwrap_or_else(Utc::now),
+            level: Some(Cow::Borrowed(priority.severity())),
+            message,
+            message_template: None,
+            exception: None,
+            additional: HashMap::new(),
+        };
+
+        // Higher-priority fields are added last so that
+        // they replace lower-priority ones
+        let (first, second) = match precedence {
+            Precedence::Header => (sd, header),
+            Precedence::StructuredData => (header, sd),
+        };
+
+        for (name, value) in first.into_iter().chain(second) {
+            clef.additional.insert(name, value);
+        }
+
+        clef
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::borrow::Cow::Borrowed;
+    use crate::test_util::{rfc5424_message, to_rfc5424, to_timestamp};
+    use proptest::prelude::*;
+
+    #[test]
+    fn precedence_is_parsed() {
+        assert_eq!(Precedence::Header, "Header".parse::<Precedence>().unwrap());
+        assert_eq!(Precedence::StructuredData, "structured_data".parse::<Precedence>().unwrap());
+        "sd".parse::<Precedence>().expect_err("should fail");
+    }
+
+    #[test]
+    fn syslog_to_clef() {
+        let expected = json!({
+            "@l": "info",
+            "@m": "hello world",
+            "@t": "2020-02-13T00:51:39.527825Z",
+            "facility": "daemon",
+            "hostname": "docker-desktop",
+            "app_name": "8b1089798cf8",
+            "proc_id": "1481",
+            "message_id": "8b1089798cf8",
+        });
+
+        let message = "hello world";
+
+        let syslog = syslog::Message {
+            priority: syslog::Priority {
+                facility: 3,
+                severity: 6,
+            },
+            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
+            hostname: Some(Borrowed("docker-desktop")),
+            app_name: Some(Borrowed("8b1089798cf8")),
+            proc_id: Some(Borrowed("1481")),
+            message_id: Some(Borrowed("8b1089798cf8")),
+            structured_data: None,
+            message: Some(Borrowed(message)),
+        };
+
+        let clef = syslog.into_clef();
+        let actual = serde_json::to_value(clef).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn syslog_to_clef_with_structured_data() {
+        let expected = json!({
+            "@l": "info",
+            "@m": "hello world",
+            "@t": "2020-02-13T00:51:39.527825Z",
+            "facility": "daemon",
+            "hostname": "docker-desktop",
+            "app_name": "8b1089798cf8",
+            "proc_id": "1481",
+            "message_id": "8b1089798cf8",
+            "sdid1234": [{ "hello": "world" }, { "event": "value" }]
+        });
+
+        let message = "hello world";
+
+        let sd_params = vec![
+            (Borrowed("hello"), "world".to_owned()),
+            (Borrowed("event"), "value".to_owned()),
+        ];
+
+        let syslog = syslog::Message {
+            priority: syslog::Priority {
+                facility: 3,
+                severity: 6,
+            },
+            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
+            hostname: Some(Borrowed("docker-desktop")),
+            app_name: Some(Borrowed("8b1089798cf8")),
+            proc_id: Some(Borrowed("1481")),
+            message_id: Some(Borrowed("8b1089798cf8")),
+            structured_data: Some(vec![syslog::StructuredDataElement {
+                id: Borrowed("sdid1234"),
+                params: sd_params,
+            }]),
+            message: Some(Borrowed(message)),
+        };
+
+        let clef = syslog.into_clef();
+        let actual = serde_json::to_value(clef).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn syslog_to_clef_with_structured_data_with_duplicated_params() {
+        let expected = json!({
+            "@l": "info",
+            "@m": "hello world",
+            "@t": "2020-02-13T00:51:39.527825Z",
+            "facility": "daemon",
+            "hostname": "docker-desktop",
+            "app_name": "8b1089798cf8",
+            "proc_id": "1481",
+            "message_id": "8b1089798cf8",
+            "sdid1234": [{ "ip": "192.0.2.1" }, { "ip": "192.0.2.129" }]
+        });
+
+        let message = "hello world";
+
+        let sd_params = vec![
+            (Borrowed("ip"), "192.0.2.1".to_owned()),
+            (Borrowed("ip"), "192.0.2.129".to_owned()),
+        ];
+
+        let syslog = syslog::Message {
+            priority: syslog::Priority {
+                facility: 3,
+                severity: 6,
+            },
+            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
+            hostname: Some(Borrowed("docker-desktop")),
+            app_name: Some(Borrowed("8b1089798cf8")),
+            proc_id: Some(Borrowed("1481")),
+            message_id: Some(Borrowed("8b1089798cf8")),
+            structured_data: Some(vec![syslog::StructuredDataElement {
+                id: Borrowed("sdid1234"),
+                params: sd_params,
+            }]),
+            message: Some(Borrowed(message)),
+        };
+
+        let clef = syslog.into_clef();
+        let actual = serde_json::to_value(clef).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn syslog_to_clef_replaces_conflicting_header_fields() {
+        let expected = json!({
+            "@l": "info",
+            "@t": "2020-02-13T00:51:39.527825Z",
+            "facility": "daemon",
+            "hostname": [{ "name": "sd" }],
+        });
+
+        let syslog = syslog::Message {
+            priority: syslog::Priority {
+                facility: 3,
+                severity: 6,
+            },
+            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
+            hostname: Some(Borrowed("docker-desktop")),
+            app_name: None,
+            proc_id: None,
+            message_id: None,
+            structured_data: Some(vec![syslog::StructuredDataElement {
+                id: Borrowed("hostname"),
+                params: vec![(Borrowed("name"), "sd".to_owned())],
+            }]),
+            message: None,
+        };
+
+        let clef = syslog.into_clef();
+        let actual = serde_json::to_value(clef).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn syslog_to_clef_with_header_precedence() {
+        let expected = json!({
+            "@l": "info",
+            "@t": "2020-02-13T00:51:39.527825Z",
+            "facility": "daemon",
+            "hostname": "docker-desktop",
+        });
+
+        let syslog = syslog::Message {
+            priority: syslog::Priority {
+                facility: 3,
+                severity: 6,
+            },
+            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
+            hostname: Some(Borrowed("docker-desktop")),
+            app_name: None,
+            proc_id: None,
+            message_id: None,
+            structured_data: Some(vec![syslog::StructuredDataElement {
+                id: Borrowed("hostname"),
+                params: vec![(Borrowed("name"), "sd".to_owned())],
+            }]),
+            message: None,
+        };
+
+        let clef = syslog.into_clef_with(Precedence::Header);
+        let actual = serde_json::to_value(clef).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn add_property_never_overwrites() {
+        let mut clef = syslog::Message::from_str("<30>1 - host - - - -").into_clef();
+
+        clef.add_property("hostname", json!(1));
+        clef.add_property("hostname", json!(2));
+
+        assert_eq!(json!("host"), clef.additional["hostname"]);
+        assert_eq!(json!(1), clef.additional["__hostname"]);
+        assert_eq!(json!(2), clef.additional["____hostname"]);
+    }
+
+    fn precedence() -> impl Strategy<Value = Precedence> {
+        prop_oneof![Just(Precedence::Header), Just(Precedence::StructuredData)]
+    }
+
+    fn conflicting_message() -> impl Strategy<Value = syslog::Message<'static>> {
+        let ids = prop_oneof![
+            Just("facility"),
+            Just("hostname"),
+            Just("__hostname"),
+            Just("app_name"),
+            Just("@t"),
+            Just("@@t"),
+            Just("origin"),
+        ];
+
+        (rfc5424_message(), proptest::collection::vec(ids, 1..8)).prop_map(|(mut msg, ids)| {
+            let sd = msg.structured_data.get_or_insert_with(Vec::new);
+            for id in ids {
+                sd.push(syslog::StructuredDataElement {
+                    id: Borrowed(id),
+                    params: vec![(Borrowed("n"), sd.len().to_string())],
+                });
+            }
+
+            msg
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn syslog_to_clef_preserves_fields(msg in rfc5424_message()) {
+            let input = to_rfc5424(&msg);
+
+            let clef = syslog::Message::from_bytes(&input).into_clef();
+            let actual = serde_json::to_value(&clef).unwrap();
+
+            prop_assert_eq!(&json!(msg.priority.severity()), &actual["@l"]);
+            prop_assert_eq!(&json!(msg.priority.facility()), &actual["facility"]);
+            prop_assert_eq!(&json!(msg.message), actual.get("@m").unwrap_or(&Value::Null));
+            if let Some(timestamp) = msg.timestamp {
+                prop_assert_eq!(timestamp, clef.timestamp);
+            }
+
+            // Generated structured data ids don't conflict with header fields
+            for (name, value) in &[
+                ("hostname", &msg.hostname),
+                ("app_name", &msg.app_name),
+                ("proc_id", &msg.proc_id),
+                ("message_id", &msg.message_id),
+            ] {
+                prop_assert_eq!(&json!(value), actual.get(name).unwrap_or(&Value::Null));
+            }
+        }
+
+        #[test]
+        fn syslog_to_clef_conflicts_keep_the_preferred_field(msg in conflicting_message(), precedence in precedence()) {
+            let mut header = vec![("facility".to_owned(), json!(msg.priority.facility()))];
+            for (name, value) in &[
+                ("hostname", &msg.hostname),
+                ("app_name", &msg.app_name),
+                ("proc_id", &msg.proc_id),
+                ("message_id", &msg.message_id),
+            ] {
+                if let Some(value) = value {
+                    header.push((name.to_string(), json!(value)));
+                }
+            }
+
+            let mut sd = vec![];
+            for element in msg.structured_data.iter().flatten() {
+                let params = element
+                    .params
+                    .iter()
+                    .map(|(k, v)| json!({ k.as_ref(): v }))
+                    .collect::<Vec<_>>();
+                sd.push((element.id.to_string(), json!(params)));
+            }
+
+            // The preferred field replaces any others with the same name
+            let (lower, higher) = match precedence {
+                Precedence::Header => (sd, header),
+                Precedence::StructuredData => (header, sd),
+            };
+            let expected = lower.into_iter().chain(higher).collect::<HashMap<_, _>>();
+
+            let clef = msg.into_clef_with(precedence);
+            let actual = clef
+                .additional
+                .into_iter()
+                .map(|(k, v)| (k.into_owned(), v))
+                .collect::<HashMap<_, _>>();
+
+            prop_assert_eq!(expected, actual);
+        }
+    }
+}
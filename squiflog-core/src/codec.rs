@@ -0,0 +1,842 @@
+/*!
+Framing for SYSLOG messages sent over streams.
+
+Messages sent over a stream transport like TCP or TLS need to be framed so
+that a receiver can tell where one message ends and the next begins.
+[RFC 6587](https://tools.ietf.org/html/rfc6587) describes two methods:
+
+- Octet counting, where each message is prefixed by its length in bytes: `MSG-LEN SP SYSLOG-MSG`.
+- Non-transparent framing, where each message is terminated by a newline.
+
+These codecs can be plugged into `tokio_util::codec::Framed` to read and write
+frames on any transport.
+
+Invalid frames are decoded as `Err` items instead of failing the decoder, because
+`Framed` stops reading after the first error. The rest of an invalid frame is
+skipped, so the next item is the frame after it.
+*/
+
+use bytes::{
+    BufMut,
+    Bytes,
+    BytesMut,
+};
+
+use tokio_util::codec::{
+    Decoder,
+    Encoder,
+};
+
+use std::{
+    borrow::Cow,
+    cmp,
+};
+
+use crate::error::Error;
+
+/**
+The default maximum length of a single frame.
+*/
+pub const DEFAULT_MAX_LENGTH: usize = 64 * 1024;
+
+// The number of digits needed to represent `usize::MAX`
+const MAX_LEN_DIGITS: usize = 20;
+
+fn invalid_frame(offset: usize, msg: impl Into<Cow<'static, str>>) -> Error {
+    Error::Framing {
+        offset,
+        msg: msg.into(),
+    }
+}
+
+/**
+A codec for octet-counted frames.
+
+Each frame is prefixed by its length in ASCII digits followed by a space.
+
+Invalid frames are decoded as `Err` items. Frames that are too long are skipped
+using their length prefix. Frames with an invalid prefix are skipped up to the
+next `<digits> <` that looks like the start of a frame.
+*/
+#[derive(Debug, Clone)]
+pub struct OctetCountingCodec {
+    max_length: usize,
+    // The number of bytes consumed from the stream
+    offset: usize,
+    // Set after an invalid frame until the rest of it has been discarded
+    skip: Option<Skip>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Skip {
+    // The number of bytes left in a frame that's too long
+    Bytes(usize),
+    // Up to the next plausible length prefix
+    Resync { after_digit: bool },
+}
+
+impl OctetCountingCodec {
+    pub fn new() -> Self {
+        OctetCountingCodec::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /**
+    Create a codec that rejects frames longer than `max_length` bytes.
+    */
+    pub fn with_max_length(max_length: usize) -> Self {
+        OctetCountingCodec {
+            max_length,
+            offset: 0,
+            skip: None,
+        }
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Default for OctetCountingCodec {
+    fn default() -> Self {
+        OctetCountingCodec::new()
+    }
+}
+
+impl Decoder for OctetCountingCodec {
+    type Item = Result<Bytes, Error>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame(src).transpose())
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame_eof(src).transpose())
+    }
+}
+
+impl OctetCountingCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        if !self.discard(src, false) {
+            return Ok(None);
+        }
+
+        let digits = src.iter().take_while(|b| b.is_ascii_digit()).count();
+
+        // Wait for the rest of the length prefix
+        if digits == src.len() {
+            if digits > MAX_LEN_DIGITS {
+                return Err(self.resync(src, 0, digits, "length prefix is too long"));
+            }
+
+            return Ok(None);
+        }
+
+        if digits == 0 || src[digits] != b' ' {
+            return Err(self.resync(
+                src,
+                digits,
+                digits.max(1),
+                "expected a length prefix followed by a space",
+            ));
+        }
+
+        let len = match std::str::from_utf8(&src[..digits]).ok().and_then(|len| len.parse::<usize>().ok()) {
+            Some(len) => len,
+            None => return Err(self.resync(src, 0, digits, "length prefix is too long")),
+        };
+
+        let header = digits + 1;
+        if len > self.max_length {
+            let err = invalid_frame(
+                self.offset,
+                format!("length {} exceeds the maximum of {}", len, self.max_length),
+            );
+            self.skip = Some(Skip::Bytes(header.saturating_add(len)));
+
+            return Err(err);
+        }
+
+        // Wait for the rest of the frame
+        if src.len() < header + len {
+            src.reserve(header + len - src.len());
+            return Ok(None);
+        }
+
+        self.offset += header + len;
+
+        let _ = src.split_to(header);
+        Ok(Some(src.split_to(len).freeze()))
+    }
+
+    fn decode_frame_eof(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        self.discard(src, true);
+
+        match self.decode_frame(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => {
+                let err = invalid_frame(self.offset + src.len(), "unexpected end of input");
+
+                let len = src.len();
+                self.advance(src, len);
+
+                Err(err)
+            }
+        }
+    }
+
+    // Skip an invalid frame up to the next plausible length prefix
+    fn resync(&mut self, src: &mut BytesMut, at: usize, skip: usize, msg: impl Into<Cow<'static, str>>) -> Error {
+        let err = invalid_frame(self.offset + at, msg);
+
+        let after_digit = src[skip - 1].is_ascii_digit();
+        self.advance(src, skip);
+        self.skip = Some(Skip::Resync { after_digit });
+
+        err
+    }
+
+    /**
+    Discard the rest of an invalid frame.
+
+    Returns `false` if more input is needed to find the end of it. At the end of
+    the input, whatever is left of the frame is discarded.
+    */
+    fn discard(&mut self, src: &mut BytesMut, eof: bool) -> bool {
+        let done = match self.skip {
+            None => return true,
+            Some(Skip::Bytes(remaining)) => {
+                let skip = cmp::min(remaining, src.len());
+                self.advance(src, skip);
+
+                self.skip = Some(Skip::Bytes(remaining - skip));
+                skip == remaining
+            }
+            Some(Skip::Resync { after_digit }) => {
+                let (skip, found) = match next_frame(src, after_digit) {
+                    Ok(at) => (at, true),
+                    Err(at) => (at, false),
+                };
+
+                let after_digit = if skip > 0 {
+                    src[skip - 1].is_ascii_digit()
+                } else {
+                    after_digit
+                };
+                self.advance(src, skip);
+
+                self.skip = Some(Skip::Resync { after_digit });
+                found
+            }
+        };
+
+        if done || eof {
+            self.skip = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn advance(&mut self, src: &mut BytesMut, len: usize) {
+        let _ = src.split_to(len);
+        self.offset += len;
+    }
+}
+
+/**
+Find the next thing that looks like the start of an octet-counted frame, like `11 <`.
+
+Returns `Err` with the number of bytes that can be discarded if more input is
+needed to tell. A run of digits at the start of `src` only counts if it doesn't
+continue one from the previous input.
+*/
+fn next_frame(src: &[u8], after_digit: bool) -> Result<usize, usize> {
+    let mut i = 0;
+    while i < src.len() {
+        let digits = src[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            i += 1;
+            continue;
+        }
+
+        // Whole runs of digits are skipped, so any after the start follow something else
+        let continues = i == 0 && after_digit;
+        if !continues && digits <= MAX_LEN_DIGITS {
+            match &src[i + digits..] {
+                [b' ', b'<', ..] => return Ok(i),
+                [] | [b' '] => return Err(i),
+                _ => (),
+            }
+        }
+
+        i += digits;
+    }
+
+    Err(src.len())
+}
+
+impl<'a> Encoder<&'a [u8]> for OctetCountingCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &'a [u8], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.len() > self.max_length {
+            return Err(invalid_frame(
+                dst.len(),
+                format!("length {} exceeds the maximum of {}", item.len(), self.max_length),
+            ));
+        }
+
+        let len = item.len().to_string();
+
+        dst.reserve(len.len() + 1 + item.len());
+        dst.put_slice(len.as_bytes());
+        dst.put_u8(b' ');
+        dst.put_slice(item);
+
+        Ok(())
+    }
+}
+
+impl Encoder<Bytes> for OctetCountingCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&*item, dst)
+    }
+}
+
+/**
+A codec for newline-terminated frames.
+
+A trailing `\r` before the newline is removed from each frame. Frames can't
+contain newlines themselves, so the encoder will reject them.
+
+A frame that's too long is decoded as an `Err` item, and the rest of it is
+discarded up to and including its newline.
+*/
+#[derive(Debug, Clone)]
+pub struct NewlineCodec {
+    max_length: usize,
+    // The index to resume searching for a newline from
+    next_index: usize,
+    // The number of bytes consumed from the stream
+    offset: usize,
+    // Whether the rest of a frame that's too long is being discarded
+    is_discarding: bool,
+}
+
+impl NewlineCodec {
+    pub fn new() -> Self {
+        NewlineCodec::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /**
+    Create a codec that rejects frames longer than `max_length` bytes.
+    */
+    pub fn with_max_length(max_length: usize) -> Self {
+        NewlineCodec {
+            max_length,
+            next_index: 0,
+            offset: 0,
+            is_discarding: false,
+        }
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Default for NewlineCodec {
+    fn default() -> Self {
+        NewlineCodec::new()
+    }
+}
+
+impl Decoder for NewlineCodec {
+    type Item = Result<Bytes, Error>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame(src).transpose())
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame_eof(src).transpose())
+    }
+}
+
+impl NewlineCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        if !self.discard(src, false) {
+            return Ok(None);
+        }
+
+        // Only look for a newline as far as the maximum length
+        let read_to = cmp::min(self.max_length.saturating_add(1), src.len());
+
+        match src[self.next_index..read_to].iter().position(|b| *b == b'\n') {
+            Some(offset) => {
+                let end = self.next_index + offset;
+                self.next_index = 0;
+                self.offset += end + 1;
+
+                let mut frame = src.split_to(end + 1);
+                frame.truncate(end);
+                if frame.last() == Some(&b'\r') {
+                    frame.truncate(end - 1);
+                }
+
+                Ok(Some(frame.freeze()))
+            }
+            None if src.len() > self.max_length => {
+                self.next_index = 0;
+                self.is_discarding = true;
+
+                Err(invalid_frame(
+                    self.offset,
+                    format!("no newline within the maximum length of {}", self.max_length),
+                ))
+            }
+            None => {
+                self.next_index = read_to;
+
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_frame_eof(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        self.discard(src, true);
+
+        match self.decode_frame(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            // The last frame doesn't need a trailing newline
+            None => {
+                self.next_index = 0;
+                self.offset += src.len();
+
+                let mut frame = src.split_to(src.len());
+                if frame.last() == Some(&b'\r') {
+                    frame.truncate(frame.len() - 1);
+                }
+
+                Ok(Some(frame.freeze()))
+            }
+        }
+    }
+
+    /**
+    Discard a frame that's too long up to and including its newline.
+
+    Returns `false` if more input is needed to find the newline. At the end of
+    the input, whatever is left of the frame is discarded.
+    */
+    fn discard(&mut self, src: &mut BytesMut, eof: bool) -> bool {
+        if !self.is_discarding {
+            return true;
+        }
+
+        let (len, done) = match src.iter().position(|b| *b == b'\n') {
+            Some(end) => (end + 1, true),
+            None => (src.len(), eof),
+        };
+
+        let _ = src.split_to(len);
+        self.offset += len;
+
+        self.is_discarding = !done;
+        done
+    }
+}
+
+impl<'a> Encoder<&'a [u8]> for NewlineCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &'a [u8], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.contains(&b'\n') {
+            return Err(invalid_frame(dst.len(), "newline-terminated frames can't contain newlines"));
+        }
+
+        if item.len() > self.max_length {
+            return Err(invalid_frame(
+                dst.len(),
+                format!("length {} exceeds the maximum of {}", item.len(), self.max_length),
+            ));
+        }
+
+        dst.reserve(item.len() + 1);
+        dst.put_slice(item);
+        dst.put_u8(b'\n');
+
+        Ok(())
+    }
+}
+
+impl Encoder<Bytes> for NewlineCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&*item, dst)
+    }
+}
+
+/**
+A codec that accepts both octet-counted and newline-terminated frames.
+
+The framing of each frame is detected from its first byte: a SYSLOG message
+always starts with `<`, so a leading digit means the frame is octet-counted.
+Frames are always encoded using octet counting, because it can carry any message.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SyslogCodec {
+    octet_counting: OctetCountingCodec,
+    newline: NewlineCodec,
+    current: Option<Framing>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Framing {
+    OctetCounting,
+    Newline,
+}
+
+impl SyslogCodec {
+    pub fn new() -> Self {
+        SyslogCodec::default()
+    }
+
+    /**
+    Create a codec that rejects frames longer than `max_length` bytes.
+    */
+    pub fn with_max_length(max_length: usize) -> Self {
+        SyslogCodec {
+            octet_counting: OctetCountingCodec::with_max_length(max_length),
+            newline: NewlineCodec::with_max_length(max_length),
+            current: None,
+        }
+    }
+
+    fn framing(&mut self, src: &BytesMut) -> Option<Framing> {
+        if self.current.is_none() {
+            self.current = src.first().map(|b| {
+                if b.is_ascii_digit() {
+                    Framing::OctetCounting
+                } else {
+                    Framing::Newline
+                }
+            });
+
+            // Frames alternate between codecs, so keep their positions in the stream in sync
+            let offset = self.octet_counting.offset.max(self.newline.offset);
+            self.octet_counting.offset = offset;
+            self.newline.offset = offset;
+        }
+
+        self.current
+    }
+
+    // Finish discarding an invalid frame before detecting the framing of the next one
+    fn discard(&mut self, src: &mut BytesMut, eof: bool) -> bool {
+        self.octet_counting.discard(src, eof) && self.newline.discard(src, eof)
+    }
+}
+
+impl Decoder for SyslogCodec {
+    type Item = Result<Bytes, Error>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.discard(src, false) {
+            return Ok(None);
+        }
+
+        let decoded = match self.framing(src) {
+            Some(Framing::OctetCounting) => self.octet_counting.decode(src),
+            Some(Framing::Newline) => self.newline.decode(src),
+            None => return Ok(None),
+        };
+
+        // Detect the framing again for the next frame
+        if !matches!(decoded, Ok(None)) {
+            self.current = None;
+        }
+
+        decoded
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.discard(src, true);
+
+        let decoded = match self.framing(src) {
+            Some(Framing::OctetCounting) => self.octet_counting.decode_eof(src),
+            Some(Framing::Newline) => self.newline.decode_eof(src),
+            None => return Ok(None),
+        };
+
+        self.current = None;
+
+        decoded
+    }
+}
+
+impl<'a> Encoder<&'a [u8]> for SyslogCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &'a [u8], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.octet_counting.encode(item, dst)
+    }
+}
+
+impl Encoder<Bytes> for SyslogCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.octet_counting.encode(item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        StreamExt,
+    };
+    use tokio_util::codec::FramedRead;
+
+    fn decode_all(codec: &mut impl Decoder<Item = Result<Bytes, Error>, Error = Error>, input: &[u8]) -> Vec<Bytes> {
+        let mut src = BytesMut::from(input);
+        let mut frames = vec![];
+
+        while let Some(frame) = frame(codec.decode(&mut src)) {
+            frames.push(frame);
+        }
+        while let Some(frame) = frame(codec.decode_eof(&mut src)) {
+            frames.push(frame);
+        }
+
+        frames
+    }
+
+    // The next valid frame, if there's enough input for one
+    fn frame(item: Result<Option<Result<Bytes, Error>>, Error>) -> Option<Bytes> {
+        item.expect("failed to decode").map(|frame| frame.expect("invalid frame"))
+    }
+
+    // The error for the next frame, which should be invalid
+    fn invalid(item: Result<Option<Result<Bytes, Error>>, Error>) -> Error {
+        item.expect("failed to decode")
+            .expect("missing frame")
+            .expect_err("should fail")
+    }
+
+    #[test]
+    fn octet_counting_decodes_frames() {
+        let frames = decode_all(&mut OctetCountingCodec::new(), b"11 <34>1 hello5 <1>1 ");
+
+        assert_eq!(vec![&b"<34>1 hello"[..], &b"<1>1 "[..]], frames);
+    }
+
+    #[test]
+    fn octet_counting_waits_for_complete_frames() {
+        let mut codec = OctetCountingCodec::new();
+        let mut src = BytesMut::from(&b"1"[..]);
+
+        assert_eq!(None, frame(codec.decode(&mut src)));
+
+        src.extend_from_slice(b"1 <34>1 ");
+        assert_eq!(None, frame(codec.decode(&mut src)));
+
+        src.extend_from_slice(b"hello");
+        assert_eq!(
+            Some(Bytes::from_static(b"<34>1 hello")),
+            frame(codec.decode(&mut src))
+        );
+    }
+
+    #[test]
+    fn octet_counting_rejects_invalid_prefixes() {
+        let cases = [&b"<34>1 hello"[..], &b"11<34>1 hello"[..], &b" 11 <34>1 hello"[..]];
+
+        for case in &cases {
+            let mut src = BytesMut::from(*case);
+            invalid(OctetCountingCodec::new().decode(&mut src));
+        }
+    }
+
+    #[test]
+    fn octet_counting_rejects_long_frames() {
+        let mut src = BytesMut::from(&b"11 <34>1 hello"[..]);
+
+        invalid(OctetCountingCodec::with_max_length(10).decode(&mut src));
+    }
+
+    #[test]
+    fn octet_counting_rejects_truncated_frames() {
+        let mut src = BytesMut::from(&b"11 <34>1"[..]);
+
+        invalid(OctetCountingCodec::new().decode_eof(&mut src));
+    }
+
+    #[test]
+    fn octet_counting_skips_long_frames_across_reads() {
+        let mut codec = OctetCountingCodec::with_max_length(10);
+        let mut src = BytesMut::from(&b"11 <34>1 hel"[..]);
+
+        invalid(codec.decode(&mut src));
+        assert_eq!(None, frame(codec.decode(&mut src)));
+
+        src.extend_from_slice(b"lo5 <1>1 ");
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ")), frame(codec.decode(&mut src)));
+        assert_eq!(None, frame(codec.decode_eof(&mut src)));
+    }
+
+    #[test]
+    fn octet_counting_resumes_after_invalid_prefixes() {
+        let mut codec = OctetCountingCodec::new();
+        let mut src = BytesMut::from(&b"11<34>1 hello 12 world5 <1>1 "[..]);
+
+        let err = invalid(codec.decode(&mut src));
+        assert!(matches!(err, Error::Framing { offset: 2, .. }), "{}", err);
+
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ")), frame(codec.decode(&mut src)));
+        assert_eq!(None, frame(codec.decode_eof(&mut src)));
+    }
+
+    #[test]
+    fn octet_counting_resumes_after_long_prefixes() {
+        let mut codec = OctetCountingCodec::new();
+        let mut src = BytesMut::from(&b"1111111111111111111111"[..]);
+
+        invalid(codec.decode(&mut src));
+
+        // The rest of the prefix isn't mistaken for a new one
+        src.extend_from_slice(b"1 <34>1 hello5 <1>1 ");
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ")), frame(codec.decode(&mut src)));
+    }
+
+    #[test]
+    fn newline_decodes_frames() {
+        let frames = decode_all(&mut NewlineCodec::new(), b"<34>1 hello\n<34>1 world\r\n<1>1 last");
+
+        assert_eq!(vec![&b"<34>1 hello"[..], &b"<34>1 world"[..], &b"<1>1 last"[..]], frames);
+    }
+
+    #[test]
+    fn newline_rejects_long_frames() {
+        let mut src = BytesMut::from(&b"<34>1 hello"[..]);
+
+        invalid(NewlineCodec::with_max_length(10).decode(&mut src));
+    }
+
+    #[test]
+    fn newline_discards_long_frames_across_reads() {
+        let mut codec = NewlineCodec::with_max_length(10);
+        let mut src = BytesMut::from(&b"<34>1 hel"[..]);
+
+        assert_eq!(None, frame(codec.decode(&mut src)));
+
+        src.extend_from_slice(b"lo, this is");
+        let err = invalid(codec.decode(&mut src));
+        assert!(matches!(err, Error::Framing { offset: 0, .. }), "{}", err);
+        assert_eq!(None, frame(codec.decode(&mut src)));
+
+        src.extend_from_slice(b" too long");
+        assert_eq!(None, frame(codec.decode(&mut src)));
+
+        src.extend_from_slice(b"\n<1>1 ok\n<1>1 last");
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ok")), frame(codec.decode(&mut src)));
+        assert_eq!(None, frame(codec.decode(&mut src)));
+        assert_eq!(
+            Some(Bytes::from_static(b"<1>1 last")),
+            frame(codec.decode_eof(&mut src))
+        );
+    }
+
+    #[test]
+    fn syslog_resumes_after_invalid_frames() {
+        let mut codec = SyslogCodec::with_max_length(10);
+        let mut src = BytesMut::from(&b"<34>1 hello world\n5 <1>1 11 <34>1 hello<1>1 ok\n"[..]);
+
+        invalid(codec.decode(&mut src));
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ")), frame(codec.decode(&mut src)));
+
+        let err = invalid(codec.decode(&mut src));
+        assert!(matches!(err, Error::Framing { offset: 25, .. }), "{}", err);
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ok")), frame(codec.decode(&mut src)));
+        assert_eq!(None, frame(codec.decode_eof(&mut src)));
+    }
+
+    #[test]
+    fn framed_reads_continue_after_invalid_frames() {
+        let input = &b"5 <1>1 <34>1 hello world\n11<34>1 hello 12 world5 <1>1 <1>1 ok\n99 <1>1"[..];
+        let frames = block_on(FramedRead::new(input, SyslogCodec::with_max_length(10)).collect::<Vec<_>>());
+
+        let frames = frames
+            .into_iter()
+            .map(|frame| match frame.expect("failed to decode") {
+                Ok(frame) => Ok(frame),
+                Err(err) => Err(err.offset()),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Ok(Bytes::from_static(b"<1>1 ")),
+                Err(Some(7)),
+                Err(Some(27)),
+                Ok(Bytes::from_static(b"<1>1 ")),
+                Ok(Bytes::from_static(b"<1>1 ok")),
+                Err(Some(62)),
+            ],
+            frames
+        );
+    }
+
+    #[test]
+    fn syslog_detects_framing_per_frame() {
+        let frames = decode_all(&mut SyslogCodec::new(), b"11 <34>1 hello<34>1 world\n5 <1>1 ");
+
+        assert_eq!(vec![&b"<34>1 hello"[..], &b"<34>1 world"[..], &b"<1>1 "[..]], frames);
+    }
+
+    #[test]
+    fn encoded_frames_roundtrip() {
+        let mut dst = BytesMut::new();
+
+        let mut octet_counting = OctetCountingCodec::new();
+        octet_counting.encode(&b"<34>1 hello\nworld"[..], &mut dst).expect("failed to encode");
+        assert_eq!(&b"17 <34>1 hello\nworld"[..], &dst[..]);
+        assert_eq!(vec![&b"<34>1 hello\nworld"[..]], decode_all(&mut octet_counting, &dst));
+
+        dst.clear();
+
+        let mut newline = NewlineCodec::new();
+        newline.encode(&b"<34>1 hello"[..], &mut dst).expect("failed to encode");
+        assert_eq!(&b"<34>1 hello\n"[..], &dst[..]);
+        assert_eq!(vec![&b"<34>1 hello"[..]], decode_all(&mut newline, &dst));
+    }
+
+    #[test]
+    fn framing_errors_locate_the_failure() {
+        let mut codec = SyslogCodec::new();
+        let mut src = BytesMut::from(&b"5 <1>1 <34>1 hello\n11<34>1 hello"[..]);
+
+        assert_eq!(Some(Bytes::from_static(b"<1>1 ")), frame(codec.decode(&mut src)));
+        assert_eq!(Some(Bytes::from_static(b"<34>1 hello")), frame(codec.decode(&mut src)));
+
+        let err = invalid(codec.decode(&mut src));
+        assert!(matches!(err, Error::Framing { offset: 21, .. }), "{}", err);
+    }
+
+    #[test]
+    fn newline_rejects_encoding_newlines() {
+        let mut dst = BytesMut::new();
+
+        NewlineCodec::new().encode(&b"hello\nworld"[..], &mut dst).expect_err("should fail");
+    }
+}
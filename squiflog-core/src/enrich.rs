@@ -0,0 +1,225 @@
+/*!
+Enrichment stages applied to CLEF events before they're emitted.
+
+Enrichers run in the order they're registered, and each sees the changes
+made by the ones before it. The built-in enrichers for adding, renaming,
+and removing properties are implemented on the same [`Enricher`] trait as
+custom ones, and closures can be used as enrichers with [`from_fn`]:
+
+```
+use squiflog_core::{clef, enrich::{self, Enricher}, syslog};
+
+let enrichers: Vec<Box<dyn Enricher>> = vec![
+    Box::new(enrich::rename("hostname", "host")),
+    Box::new(enrich::remove("facility")),
+    Box::new(enrich::from_fn(|evt: &mut clef::Message| evt.level = Some("warning".into()))),
+];
+
+let mut evt = syslog::Message::from_bytes(b"<30>1 - docker-desktop - - - - hello world").into_clef();
+for enricher in &enrichers {
+    enricher.enrich(&mut evt);
+}
+
+assert_eq!(Some("docker-desktop"), evt.additional["host"].as_str());
+assert!(!evt.additional.contains_key("facility"));
+```
+*/
+
+use serde_json::Value;
+
+use crate::clef;
+
+/**
+An enrichment stage for CLEF events.
+
+Enrichers are shared between all messages being processed,
+so they need to synchronize any internal state themselves.
+*/
+pub trait Enricher: Send + Sync + 'static {
+    /**
+    Enrich a single event.
+    */
+    fn enrich(&self, evt: &mut clef::Message);
+}
+
+impl<E> Enricher for Box<E>
+where
+    E: Enricher + ?Sized,
+{
+    fn enrich(&self, evt: &mut clef::Message) {
+        (**self).enrich(evt)
+    }
+}
+
+/**
+Use a closure as an enricher.
+*/
+pub fn from_fn<F>(f: F) -> FromFn<F>
+where
+    F: Fn(&mut clef::Message) + Send + Sync + 'static,
+{
+    FromFn(f)
+}
+
+/**
+Attach a property to every event.
+
+The property never replaces one that's already on the event.
+See [`clef::Message::add_property`] for how conflicts are resolved.
+*/
+pub fn property(name: impl Into<String>, value: impl Into<Value>) -> Property {
+    Property {
+        name: name.into(),
+        value: value.into(),
+    }
+}
+
+/**
+Rename a property on every event that has it.
+
+The renamed property never replaces one that's already on the event.
+*/
+pub fn rename(from: impl Into<String>, to: impl Into<String>) -> Rename {
+    Rename {
+        from: from.into(),
+        to: to.into(),
+    }
+}
+
+/**
+Remove a property from every event that has it.
+*/
+pub fn remove(name: impl Into<String>) -> Remove {
+    Remove { name: name.into() }
+}
+
+/**
+An enricher that calls a closure.
+*/
+#[derive(Debug, Clone)]
+pub struct FromFn<F>(F);
+
+impl<F> Enricher for FromFn<F>
+where
+    F: Fn(&mut clef::Message) + Send + Sync + 'static,
+{
+    fn enrich(&self, evt: &mut clef::Message) {
+        (self.0)(evt)
+    }
+}
+
+/**
+An enricher that attaches a property to every event.
+*/
+#[derive(Debug, Clone)]
+pub struct Property {
+    name: String,
+    value: Value,
+}
+
+impl Enricher for Property {
+    fn enrich(&self, evt: &mut clef::Message) {
+        evt.add_property(self.name.clone(), self.value.clone());
+    }
+}
+
+/**
+An enricher that renames a property.
+*/
+#[derive(Debug, Clone)]
+pub struct Rename {
+    from: String,
+    to: String,
+}
+
+impl Enricher for Rename {
+    fn enrich(&self, evt: &mut clef::Message) {
+        if self.from == self.to {
+            return;
+        }
+
+        if let Some(value) = evt.additional.remove(&*self.from) {
+            evt.add_property(self.to.clone(), value);
+        }
+    }
+}
+
+/**
+An enricher that removes a property.
+*/
+#[derive(Debug, Clone)]
+pub struct Remove {
+    name: String,
+}
+
+impl Enricher for Remove {
+    fn enrich(&self, evt: &mut clef::Message) {
+        evt.additional.remove(&*self.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syslog;
+
+    fn event() -> clef::Message<'static> {
+        syslog::Message::from_bytes(b"<30>1 2020-02-13T00:51:39Z docker-desktop app - - - hello world")
+            .into_clef()
+            .into_owned()
+    }
+
+    #[test]
+    fn property_never_replaces_existing() {
+        let mut evt = event();
+
+        property("environment", "test").enrich(&mut evt);
+        property("hostname", "configured").enrich(&mut evt);
+
+        assert_eq!("test", evt.additional["environment"]);
+        assert_eq!("docker-desktop", evt.additional["hostname"]);
+        assert_eq!("configured", evt.additional["__hostname"]);
+    }
+
+    #[test]
+    fn rename_moves_property() {
+        let mut evt = event();
+
+        rename("hostname", "host").enrich(&mut evt);
+        rename("missing", "other").enrich(&mut evt);
+
+        assert_eq!("docker-desktop", evt.additional["host"]);
+        assert!(!evt.additional.contains_key("hostname"));
+        assert!(!evt.additional.contains_key("other"));
+    }
+
+    #[test]
+    fn rename_never_replaces_existing() {
+        let mut evt = event();
+
+        rename("hostname", "app_name").enrich(&mut evt);
+
+        assert_eq!("app", evt.additional["app_name"]);
+        assert_eq!("docker-desktop", evt.additional["__app_name"]);
+    }
+
+    #[test]
+    fn enrichers_run_in_order() {
+        let enrichers: Vec<Box<dyn Enricher>> = vec![
+            Box::new(rename("hostname", "host")),
+            Box::new(from_fn(|evt: &mut clef::Message| {
+                let host = evt.additional["host"].as_str().unwrap().to_uppercase();
+                evt.add_property("upper", host.into());
+            })),
+            Box::new(remove("host")),
+        ];
+
+        let mut evt = event();
+        for enricher in &enrichers {
+            enricher.enrich(&mut evt);
+        }
+
+        assert_eq!("DOCKER-DESKTOP", evt.additional["upper"]);
+        assert!(!evt.additional.contains_key("host"));
+    }
+}
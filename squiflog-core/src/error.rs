@@ -0,0 +1,138 @@
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{boxed::Box, error};
+
+/**
+An error encountered parsing or converting a SYSLOG message.
+
+Errors parsing a message carry the byte offset into the input where
+parsing failed, so the exact cause can be reported.
+*/
+#[non_exhaustive]
+pub enum Error {
+    /**
+    A message couldn't be split from a stream.
+
+    The offset is from the start of the stream.
+    */
+    Framing {
+        offset: usize,
+        msg: Cow<'static, str>,
+    },
+    /**
+    The SYSLOG header is invalid.
+    */
+    Header {
+        offset: usize,
+        msg: Cow<'static, str>,
+    },
+    /**
+    The SYSLOG structured data is invalid.
+    */
+    StructuredData {
+        offset: usize,
+        msg: Cow<'static, str>,
+    },
+    /**
+    Part of the message that should be UTF-8 isn't.
+    */
+    Encoding {
+        offset: usize,
+        msg: Cow<'static, str>,
+    },
+    /**
+    Some other error, like a failure to read input or write output.
+    */
+    Other(String),
+}
+
+impl Error {
+    pub fn msg(msg: impl fmt::Display) -> Self {
+        err_msg(msg)
+    }
+
+    /**
+    The byte offset in the input where the error occurred, if it's known.
+    */
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            Error::Framing { offset, .. }
+            | Error::Header { offset, .. }
+            | Error::StructuredData { offset, .. }
+            | Error::Encoding { offset, .. } => Some(offset),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+struct Inner(Error);
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Framing { offset, msg } => write!(f, "invalid framing at byte {}: {}", offset, msg),
+            Error::Header { offset, msg } => write!(f, "invalid header at byte {}: {}", offset, msg),
+            Error::StructuredData { offset, msg } => {
+                write!(f, "invalid structured data at byte {}: {}", offset, msg)
+            }
+            Error::Encoding { offset, msg } => write!(f, "invalid encoding at byte {}: {}", offset, msg),
+            Error::Other(msg) => msg.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for Inner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Inner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Inner {}
+
+#[cfg(feature = "std")]
+impl<E> From<E> for Error
+where
+    E: error::Error,
+{
+    fn from(err: E) -> Error {
+        Error::Other(err.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for Box<dyn error::Error + Send + Sync> {
+    fn from(err: Error) -> Box<dyn error::Error + Send + Sync> {
+        Box::new(Inner(err))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for Box<dyn error::Error> {
+    fn from(err: Error) -> Box<dyn error::Error> {
+        Box::new(Inner(err))
+    }
+}
+
+pub(crate) fn err_msg(msg: impl fmt::Display) -> Error {
+    Error::Other(msg.to_string())
+}
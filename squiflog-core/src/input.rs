@@ -0,0 +1,34 @@
+/*!
+Sources of SYSLOG messages.
+*/
+
+use bytes::Bytes;
+
+use futures::{
+    future::BoxFuture,
+    stream::BoxStream,
+};
+
+use crate::error::Error;
+
+/**
+A stream of raw SYSLOG messages, one per item.
+*/
+pub type Incoming = BoxStream<'static, Result<Bytes, Error>>;
+
+/**
+A source of SYSLOG messages.
+
+An input is bound once when a server starts, producing a stream of raw
+messages. Errors receiving an individual message are yielded from the stream
+without terminating it. The server stops if the stream ends.
+
+Inputs that receive byte streams rather than datagrams can use the
+[`codec`](../codec/index.html) module to split them into messages.
+*/
+pub trait Input: Send + 'static {
+    /**
+    Start receiving messages.
+    */
+    fn bind(self) -> BoxFuture<'static, Result<Incoming, Error>>;
+}
@@ -0,0 +1,59 @@
+/*!
+Parse SYSLOG messages and convert them into CLEF.
+
+This crate contains the conversion logic used by the `squiflog` server so
+that other Rust services can convert SYSLOG into CLEF in-process and get
+exactly the same results.
+
+Messages are parsed from raw bytes into a [`syslog::Message`], which can then
+be converted into a [`clef::Message`] that serializes as CLEF-encoded JSON:
+
+```
+use squiflog_core::syslog;
+
+let msg = syslog::Message::from_bytes(b"<30>1 2020-02-13T00:51:39.527825Z docker-desktop app 1481 - - hello world");
+let clef = msg.into_clef();
+
+let json = serde_json::to_string(&clef).unwrap();
+assert!(json.contains("\"@m\":\"hello world\""));
+```
+
+Parsing with [`syslog::Message::from_bytes`] never fails: messages that aren't
+valid RFC 5424 fall back to a lenient RFC 3164 parser. Use
+[`syslog::Message::from_rfc5424_bytes`] to only accept RFC 5424 messages.
+
+# Features
+
+- `std`: RFC 3164 parsing and conversion into CLEF. Without it, the crate is `no_std` and only needs `alloc` to parse RFC 5424 messages.
+- `codec`: `tokio-util` codecs for octet-counted and newline-terminated SYSLOG framing.
+- `stream`: turn `tokio` readers and datagram sources into streams of CLEF events, and the `Input` trait for custom sources.
+*/
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(feature = "std")]
+pub mod clef;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod enrich;
+pub mod error;
+#[cfg(feature = "stream")]
+pub mod input;
+#[cfg(feature = "std")]
+pub mod output;
+mod parsers;
+#[cfg(feature = "stream")]
+pub mod stream;
+pub mod syslog;
+
+pub use self::error::Error;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,79 @@
+/*!
+Destinations for CLEF events.
+*/
+
+use std::{
+    io::{
+        self,
+        Write,
+    },
+    sync::Mutex,
+};
+
+use crate::{
+    clef,
+    error::{
+        err_msg,
+        Error,
+    },
+};
+
+/**
+A destination for CLEF events.
+
+Outputs are shared between all messages being processed,
+so they need to synchronize any internal state themselves.
+*/
+pub trait Output: Send + Sync + 'static {
+    /**
+    Emit a single event.
+    */
+    fn emit(&self, evt: &clef::Message) -> Result<(), Error>;
+}
+
+/**
+An output that writes newline-delimited CLEF to stdout.
+*/
+pub fn stdout() -> Writer<io::Stdout> {
+    Writer::new(io::stdout())
+}
+
+/**
+An output that writes newline-delimited CLEF to some writer.
+*/
+pub struct Writer<W>(Mutex<W>);
+
+impl<W> Writer<W>
+where
+    W: Write + Send + 'static,
+{
+    pub fn new(writer: W) -> Self {
+        Writer(Mutex::new(writer))
+    }
+}
+
+impl<W> Output for Writer<W>
+where
+    W: Write + Send + 'static,
+{
+    fn emit(&self, evt: &clef::Message) -> Result<(), Error> {
+        // Each event is written in a single call so events
+        // from different threads don't interleave
+        let mut buf = serde_json::to_vec(evt)?;
+        buf.push(b'\n');
+
+        let mut writer = self.0.lock().map_err(|_| err_msg("failed to lock output"))?;
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
+}
+
+impl<O> Output for Box<O>
+where
+    O: Output + ?Sized,
+{
+    fn emit(&self, evt: &clef::Message) -> Result<(), Error> {
+        (**self).emit(evt)
+    }
+}
@@ -1,42 +1,109 @@
-use crate::error::{Error, err_msg};
-use chrono::{Utc, DateTime, Local, Datelike, Timelike, TimeZone};
-use crate::data::syslog::StructuredDataElement;
+use alloc::{borrow::Cow, format, string::String, vec};
+use crate::error::Error;
+use chrono::{Utc, DateTime};
+#[cfg(feature = "std")]
+use chrono::{Local, NaiveDate, Datelike, Timelike, TimeZone};
+use crate::syslog::StructuredDataElement;
+
+type ParserResult<'a, T> = Result<(T, &'a [u8]), Failure>;
+
+/**
+A failure to parse some input.
+
+Parsers only see the input that's left, so failures record how much of it
+remained. That's turned into an offset from the start of the whole message
+once it's known.
+*/
+#[derive(Debug)]
+pub struct Failure {
+    remaining: usize,
+    encoding: bool,
+    msg: Cow<'static, str>,
+}
+
+/**
+The part of a message a failure occurred in.
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum Section {
+    Header,
+    StructuredData,
+}
 
-type ParserResult<'a, T> = Result<(T, &'a [u8]), Error>;
+impl Failure {
+    pub fn at(i: &[u8], msg: impl Into<Cow<'static, str>>) -> Self {
+        Failure {
+            remaining: i.len(),
+            encoding: false,
+            msg: msg.into(),
+        }
+    }
 
-pub fn priority(i: &[u8]) -> ParserResult<u8> {
+    fn encoding(i: &[u8], valid_up_to: usize) -> Self {
+        Failure {
+            remaining: i.len() - valid_up_to,
+            encoding: true,
+            msg: Cow::Borrowed("invalid UTF-8"),
+        }
+    }
+
+    /**
+    Convert into an error positioned in the given input.
+
+    The input must end where the input given to the failed parser did.
+    */
+    pub fn into_error(self, input: &[u8], section: Section) -> Error {
+        let offset = input.len().saturating_sub(self.remaining);
+        let msg = self.msg;
+
+        match section {
+            _ if self.encoding => Error::Encoding { offset, msg },
+            Section::Header => Error::Header { offset, msg },
+            Section::StructuredData => Error::StructuredData { offset, msg },
+        }
+    }
+}
+
+// Interpret the start of the input as UTF-8
+pub fn utf8(i: &[u8], len: usize) -> Result<&str, Failure> {
+    core::str::from_utf8(&i[..len]).map_err(|err| Failure::encoding(i, err.valid_up_to()))
+}
+
+pub fn priority(i: &[u8]) -> ParserResult<'_, u8> {
     let (content, rem) = delimited(i, b'<', b'>')?;
-    if content.len() == 0 || content.iter().any(|b| !char::is_digit(*b as char, 10)) {
-        return Err(err_msg("invalid priority content"));
+    if content.is_empty() || content.iter().any(|b| !char::is_digit(*b as char, 10)) {
+        return Err(Failure::at(i, "invalid priority content"));
     }
-    let pval = std::str::from_utf8(content)?.parse::<u8>()?;
+    let pval = utf8(&i[1..], content.len())?
+        .parse::<u8>()
+        .map_err(|_| Failure::at(i, "priority is out of range"))?;
     Ok((pval, rem))
 }
 
-pub fn any_byte(i: &[u8]) -> ParserResult<u8> {
-    if i.len() == 0 {
-        Err(err_msg("unexpected end of input"))
+pub fn any_byte(i: &[u8]) -> ParserResult<'_, u8> {
+    if i.is_empty() {
+        Err(Failure::at(i, "unexpected end of input"))
     } else {
         Ok((i[0], &i[1..]))
     }
 }
 
-pub fn byte(i: &[u8], b: u8) -> ParserResult<()> {
+pub fn byte(i: &[u8], b: u8) -> ParserResult<'_, ()> {
     if let Ok((actual, rem)) = any_byte(i) {
         if actual == b {
             Ok(((), rem))
         } else {
-            Err(err_msg("unexpected byte"))
+            Err(Failure::at(i, format!("expected `{}`", b as char)))
         }
     } else {
-        Err(err_msg("expected byte, unexpected end of input"))
+        Err(Failure::at(i, format!("expected `{}`, unexpected end of input", b as char)))
     }
 }
 
-pub fn until(i: &[u8], end: u8) -> ParserResult<&[u8]> {
+pub fn until(i: &[u8], end: u8) -> ParserResult<'_, &[u8]> {
     let mut rem = i;
     let mut count = 0;
-    while rem.len() != 0 {
+    while !rem.is_empty() {
         if rem[0] == end {
             return Ok((&i[0..count], rem));
         }
@@ -44,18 +111,18 @@ pub fn until(i: &[u8], end: u8) -> ParserResult<&[u8]> {
         count += 1;
     }
 
-    Err(err_msg(format!("missing end `{}` delimiter", end as char)))
+    Err(Failure::at(rem, format!("missing end `{}` delimiter", end as char)))
 }
 
-pub fn delimited(i: &[u8], start: u8, end: u8) -> ParserResult<&[u8]> {
+pub fn delimited(i: &[u8], start: u8, end: u8) -> ParserResult<'_, &[u8]> {
     let rem = i;
-    if rem.len() == 0 || rem[0] != start {
-        return Err(err_msg("missing start delimiter"));
+    if rem.is_empty() || rem[0] != start {
+        return Err(Failure::at(rem, "missing start delimiter"));
     }
 
     let rem = &rem[1..];
-    if rem.len() == 0 {
-        return Err(err_msg("missing delimited content"));
+    if rem.is_empty() {
+        return Err(Failure::at(rem, "missing delimited content"));
     }
 
     let (content, rem) = until(rem, end)?;
@@ -63,41 +130,51 @@ pub fn delimited(i: &[u8], start: u8, end: u8) -> ParserResult<&[u8]> {
     Ok((content, &rem[1..]))
 }
 
-pub fn take(i: &[u8], count: usize) -> ParserResult<&[u8]> {
+#[cfg(feature = "std")]
+pub fn take(i: &[u8], count: usize) -> ParserResult<'_, &[u8]> {
     if i.len() < count {
-        return Err(err_msg("the input is too short"));
+        return Err(Failure::at(i, "the input is too short"));
     }
 
     Ok((&i[..count], &i[count..]))
 }
 
-pub fn iso8601_timestamp(i: &[u8]) -> ParserResult<DateTime<Utc>> {
+pub fn iso8601_timestamp(i: &[u8]) -> ParserResult<'_, DateTime<Utc>> {
     let (to_space, rem) = until(i, b' ')?; // Cheating a little here; we shouldn't need any trailing delimiter
-    let maybe_ts = std::str::from_utf8(to_space)?;
-    let utc = DateTime::parse_from_rfc3339(maybe_ts)?.with_timezone(&Utc);
+    let maybe_ts = utf8(i, to_space.len())?;
+    let utc = DateTime::parse_from_rfc3339(maybe_ts)
+        .map_err(|err| Failure::at(i, format!("invalid timestamp, {}", err)))?
+        .with_timezone(&Utc);
     Ok((utc, rem))
 }
 
-pub fn loose_timestamp<'a, 'b>(i: &'a [u8], now: &'b DateTime<Utc>) -> ParserResult<'a, DateTime<Utc>> {
+#[cfg(feature = "std")]
+pub fn loose_timestamp<'a>(i: &'a [u8], now: &DateTime<Utc>) -> ParserResult<'a, DateTime<Utc>> {
     if let Ok((iso_ts, rem)) = iso8601_timestamp(i) {
         return Ok((iso_ts, rem));
     }
 
     let (month_day_h_m_s, rem) = take(i, 15)?;
 
-    let cheat_and_allocate_a_year = std::str::from_utf8(month_day_h_m_s)?.to_string() + " 1980";
-    let local = Local.datetime_from_str(&cheat_and_allocate_a_year, "%h %d %H:%M:%S %Y")?;
+    let cheat_and_allocate_a_year = utf8(i, month_day_h_m_s.len())?.to_string() + " 1980";
+    let local = Local
+        .datetime_from_str(&cheat_and_allocate_a_year, "%h %d %H:%M:%S %Y")
+        .map_err(|err| Failure::at(i, format!("invalid timestamp, {}", err)))?;
 
-    let year_offset = if &month_day_h_m_s[0..3] == &b"Dec"[..] && now.month() == 1 {
+    let year_offset = if month_day_h_m_s[0..3] == b"Dec"[..] && now.month() == 1 {
         - 1
-    } else if &month_day_h_m_s[0..3] == &b"Jan"[..] && now.month() == 12 {
+    } else if month_day_h_m_s[0..3] == b"Jan"[..] && now.month() == 12 {
         1
     } else {
         0
     };
 
-    let with_year = Local.ymd(now.year() + year_offset, local.month(), local.day())
-        .and_hms(local.hour(), local.minute(), local.second());
+    // The date may not exist in the current year (Feb 29), and the time may not
+    // exist in the local timezone (a DST transition)
+    let with_year = NaiveDate::from_ymd_opt(now.year() + year_offset, local.month(), local.day())
+        .and_then(|date| date.and_hms_opt(local.hour(), local.minute(), local.second()))
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .ok_or_else(|| Failure::at(i, "invalid timestamp"))?;
 
     let utc = with_year.with_timezone(&Utc);
     Ok((utc, rem))
@@ -105,19 +182,19 @@ pub fn loose_timestamp<'a, 'b>(i: &'a [u8], now: &'b DateTime<Utc>) -> ParserRes
 
 // Consumes (requires) a trailing space
 pub fn header_item<'a>(i: &'a [u8], name: &'static str) -> ParserResult<'a, Option<&'a str>> {
-    let (content, rem) = until(i, b' ').map_err(|_| err_msg(format!("missing {}", name)))?;
+    let (content, rem) = until(i, b' ').map_err(|_| Failure::at(i, format!("missing {}", name)))?;
     let (_, rem) = byte(rem, b' ')?;
-    if &content[..] == &b"-"[..] {
+    if content == b"-" {
         Ok((None, rem))
     } else {
-        Ok((Some(std::str::from_utf8(content)?), rem))
+        Ok((Some(utf8(i, content.len())?), rem))
     }
 }
 
-pub fn param_value_content_char(i: &[u8]) -> ParserResult<u8> {
+pub fn param_value_content_char(i: &[u8]) -> ParserResult<'_, u8> {
     let (b, rem) = any_byte(i)?;
     if b == b'"' {
-        Err(err_msg("no param value content char found"))
+        Err(Failure::at(i, "no param value content char found"))
     } else if b == b'\\' {
         let (next, following_rem) = any_byte(rem)?;
         if next == b'\\' || next == b'\"' || next == b']' {
@@ -130,22 +207,22 @@ pub fn param_value_content_char(i: &[u8]) -> ParserResult<u8> {
     }
 }
 
-pub fn structured_data_element(i: &[u8]) -> ParserResult<StructuredDataElement> {
+pub fn structured_data_element(i: &[u8]) -> ParserResult<'_, StructuredDataElement<'_>> {
     let (_, rem) = byte(i, b'[')?;
     let (id, mut rem) = sd_name(rem)?;
 
     let mut params = vec![];
     while let Ok((_, sp_rem)) = byte(rem, b' ') {
         let (param, param_rem) = param(sp_rem)?;
-        params.push(param);
+        params.push((Cow::Borrowed(param.0), param.1));
         rem = param_rem;
     }
 
     let (_, rem) = byte(rem, b']')?;
-    Ok((StructuredDataElement{id, params}, rem))
+    Ok((StructuredDataElement{id: Cow::Borrowed(id), params}, rem))
 }
 
-pub fn param_value_content(i: &[u8]) -> ParserResult<String> {
+pub fn param_value_content(i: &[u8]) -> ParserResult<'_, String> {
     let mut bytes = vec![];
     let mut rem = i;
     let mut maybe_content = param_value_content_char(rem);
@@ -154,17 +231,21 @@ pub fn param_value_content(i: &[u8]) -> ParserResult<String> {
         rem = rest;
         maybe_content = param_value_content_char(rem);
     }
-    Ok((std::str::from_utf8(&bytes[..])?.into(), rem))
+
+    // Escapes mean the content doesn't map byte-for-byte onto the input,
+    // so invalid UTF-8 is reported at the start of the value
+    let content = String::from_utf8(bytes).map_err(|_| Failure::encoding(i, 0))?;
+    Ok((content, rem))
 }
 
-pub fn param_value(i: &[u8]) -> ParserResult<String> {
+pub fn param_value(i: &[u8]) -> ParserResult<'_, String> {
     let (_, rem) = byte(i, b'"')?;
     let (content, rem) = param_value_content(rem)?;
     let (_, rem) = byte(rem, b'"')?;
     Ok((content, rem))
 }
 
-pub fn sd_name(i: &[u8]) -> ParserResult<&str> {
+pub fn sd_name(i: &[u8]) -> ParserResult<'_, &str> {
     let disallowed: &[u8] = &b"\" ]="[..];
     let mut rem = i;
     let mut count = 0;
@@ -178,13 +259,13 @@ pub fn sd_name(i: &[u8]) -> ParserResult<&str> {
         maybe_char = any_byte(rem);
     }
     if count == 0 {
-        Err(err_msg("missing param name"))
+        Err(Failure::at(i, "missing param name"))
     } else {
-        Ok((std::str::from_utf8(&i[..count])?, rem))
+        Ok((utf8(i, count)?, rem))
     }
 }
 
-pub fn param(i: &[u8]) -> ParserResult<(&str, String)> {
+pub fn param(i: &[u8]) -> ParserResult<'_, (&str, String)> {
     let (name, rem) = sd_name(i)?;
     let (_, rem) = byte(rem, b'=')?;
     let (value, rem) = param_value(rem)?;
@@ -208,7 +289,7 @@ mod tests {
         let cases = [&b"(test"[..], &b"test)"[..], &b" "[..], &b""[..], &b"("[..], &b")"[..]].to_vec();
         for case in cases {
             let expect_err = delimited(case, b'(', b')');
-            assert!(expect_err.is_err(), case);
+            assert!(expect_err.is_err(), "{:?}", case);
         }
     }
 
@@ -218,6 +299,13 @@ mod tests {
         loose_timestamp(ts, &Utc::now()).expect("could not parse timestamp");
     }
 
+    #[test]
+    fn loose_timestamps_must_exist_in_the_current_year() {
+        let ts = b"Feb 29 12:34:56";
+        let now = Utc.ymd(2021, 3, 1).and_hms(0, 0, 0);
+        loose_timestamp(ts, &now).expect_err("should fail");
+    }
+
     #[test]
     fn parses_iso8601_timestamps() {
         let ts = b"1985-04-12T23:20:50.52Z "; // Note end delimiter
@@ -0,0 +1,227 @@
+/*!
+Streams of CLEF events parsed from SYSLOG sources.
+
+Byte streams like TCP connections or files are framed using a
+[`SyslogCodec`](../codec/struct.SyslogCodec.html), which accepts
+both octet-counted and newline-terminated messages:
+
+```
+# futures::executor::block_on(async {
+use futures::StreamExt;
+
+let input = &b"<30>1 - host app - - - hello\n<30>1 - host app - - - world\n"[..];
+let events = squiflog_core::stream::read(input).collect::<Vec<_>>().await;
+
+assert_eq!(2, events.len());
+# });
+```
+
+Datagram sources like UDP sockets carry one message per datagram:
+
+```no_run
+# async fn run() -> Result<(), squiflog_core::Error> {
+use futures::StreamExt;
+use tokio::net::UdpSocket;
+use tokio_util::{codec::BytesCodec, udp::UdpFramed};
+
+let socket = UdpSocket::bind("0.0.0.0:514").await?;
+let datagrams = UdpFramed::new(socket, BytesCodec::new()).map(|r| r.map(|(msg, _)| msg));
+
+let mut events = squiflog_core::stream::datagrams(datagrams);
+while let Some(evt) = events.next().await {
+    println!("{}", serde_json::to_string(&evt?)?);
+}
+# Ok(())
+# }
+```
+*/
+
+use bytes::Bytes;
+
+use futures::{
+    Stream,
+    StreamExt,
+};
+
+use tokio::io::AsyncRead;
+
+use tokio_util::codec::{
+    Decoder,
+    FramedRead,
+};
+
+use crate::{
+    clef,
+    codec::SyslogCodec,
+    error::Error,
+    syslog,
+};
+
+/**
+Read CLEF events from a stream of bytes.
+
+Messages may be either octet-counted or newline-terminated. Invalid frames are
+`Err` items, and reading continues with the next frame. An error reading from
+`reader` ends the stream.
+*/
+pub fn read(
+    reader: impl AsyncRead,
+) -> impl Stream<Item = Result<clef::Message<'static>, Error>> {
+    read_with(reader, SyslogCodec::new(), convert)
+}
+
+/**
+Read CLEF events from a stream of bytes, using the given codec to split it into
+messages and the given function to convert each message into CLEF.
+
+The conversion can choose how messages are parsed and which fields take precedence:
+
+```
+# futures::executor::block_on(async {
+use futures::StreamExt;
+use squiflog_core::{clef::Precedence, codec::NewlineCodec, stream, syslog};
+
+let input = &b"<30>1 - host app - - [hostname n=\"1\"] hello\n"[..];
+let events = stream::read_with(input, NewlineCodec::new(), |msg| {
+    // Only accept RFC 5424, and keep header fields over structured data
+    Ok(syslog::Message::from_rfc5424_bytes(msg)?
+        .into_clef_with(Precedence::Header)
+        .into_owned())
+})
+.collect::<Vec<_>>()
+.await;
+
+assert_eq!("host", events[0].as_ref().unwrap().additional["hostname"]);
+# });
+```
+*/
+pub fn read_with<C, F>(
+    reader: impl AsyncRead,
+    codec: C,
+    mut convert: F,
+) -> impl Stream<Item = Result<clef::Message<'static>, Error>>
+where
+    C: Decoder<Item = Result<Bytes, Error>, Error = Error>,
+    F: FnMut(&[u8]) -> Result<clef::Message<'static>, Error>,
+{
+    // Invalid frames are items, so the stream keeps going after them
+    FramedRead::new(reader, codec).map(move |frame| {
+        let frame = frame??;
+
+        convert(&frame)
+    })
+}
+
+/**
+Read CLEF events from a stream of datagrams, where each datagram is a single message.
+
+Errors from `source` are passed along, and reading continues for as long as it
+produces items.
+*/
+pub fn datagrams<B, E>(
+    source: impl Stream<Item = Result<B, E>>,
+) -> impl Stream<Item = Result<clef::Message<'static>, Error>>
+where
+    B: AsRef<[u8]>,
+    Error: From<E>,
+{
+    datagrams_with(source, convert)
+}
+
+/**
+Read CLEF events from a stream of datagrams, using the given function to convert
+each one into CLEF.
+*/
+pub fn datagrams_with<B, E, F>(
+    source: impl Stream<Item = Result<B, E>>,
+    mut convert: F,
+) -> impl Stream<Item = Result<clef::Message<'static>, Error>>
+where
+    B: AsRef<[u8]>,
+    Error: From<E>,
+    F: FnMut(&[u8]) -> Result<clef::Message<'static>, Error>,
+{
+    source.map(move |msg| {
+        let msg = msg?;
+
+        convert(msg.as_ref())
+    })
+}
+
+/**
+Convert a message into CLEF the default way.
+
+RFC 5424 messages are accepted, falling back to RFC 3164 for anything else,
+and structured data replaces header fields with the same name.
+*/
+pub fn convert(msg: &[u8]) -> Result<clef::Message<'static>, Error> {
+    Ok(syslog::Message::from_bytes(msg).into_clef().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream,
+    };
+    use crate::codec::NewlineCodec;
+    use std::io;
+
+    #[test]
+    fn read_parses_framed_messages() {
+        let input = &b"28 <30>1 - host app - - - hello<30>1 - host app - - - world\n"[..];
+
+        let events = block_on(read(input).collect::<Vec<_>>());
+        let messages = events
+            .into_iter()
+            .map(|evt| evt.expect("failed to read event").message.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["hello", "world"], messages);
+    }
+
+    #[test]
+    fn read_continues_after_invalid_frames() {
+        let input = &b"5 <1>1 11<34>1 hello 28 <30>1 - host app - - - world"[..];
+
+        let events = block_on(read(input).collect::<Vec<_>>());
+
+        assert_eq!(3, events.len());
+        events[0].as_ref().expect("failed to read event");
+        assert!(matches!(events[1], Err(Error::Framing { offset: 9, .. })), "{:?}", events[1]);
+        assert_eq!(Some("world"), events[2].as_ref().expect("failed to read event").message.as_deref());
+    }
+
+    #[test]
+    fn read_with_uses_the_conversion() {
+        let input = &b"<30>1 - host app - - - hello\nnot rfc 5424\n"[..];
+
+        let events = block_on(
+            read_with(input, NewlineCodec::new(), |msg| {
+                Ok(syslog::Message::from_rfc5424_bytes(msg)?.into_clef().into_owned())
+            })
+            .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(2, events.len());
+        events[0].as_ref().expect("failed to read event");
+        events[1].as_ref().expect_err("should fail");
+    }
+
+    #[test]
+    fn datagrams_parses_each_datagram() {
+        let input = stream::iter(vec![
+            Ok(&b"<30>1 - host app - - - hello"[..]),
+            Err(io::Error::other("failed to receive")),
+        ]);
+
+        let mut events = block_on(datagrams(input).collect::<Vec<_>>()).into_iter();
+
+        let evt = events.next().unwrap().expect("failed to read event");
+        assert_eq!(Some("hello"), evt.message.as_deref());
+        assert_eq!(Some("host"), evt.additional["hostname"].as_str());
+
+        events.next().unwrap().expect_err("should fail");
+    }
+}
@@ -1,14 +1,20 @@
 use crate::{
-    error::{
-        err_msg,
-        Error,
+    error::Error,
+    parsers::{
+        self,
+        Failure,
+        Section,
     },
-    data::parsers
 };
-use std::borrow::Cow;
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec,
+    vec::Vec,
+};
 use chrono::{Utc, DateTime};
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Priority {
     pub facility: u8,
     pub severity: u8,
@@ -66,35 +72,94 @@ impl Priority {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct StructuredDataElement<'a> {
-    pub id: &'a str,
-    pub params: Vec<(&'a str, String)>,
+    pub id: Cow<'a, str>,
+    pub params: Vec<(Cow<'a, str>, String)>,
+}
+
+impl<'a> StructuredDataElement<'a> {
+    /**
+    Parse a single structured data element, like `[exampleSDID@32473 iut="3"]`.
+    */
+    pub fn from_bytes(s: &'a [u8]) -> Result<Self, Error> {
+        let sd = |err: Failure| err.into_error(s, Section::StructuredData);
+
+        let (r, rem) = parsers::structured_data_element(s).map_err(sd)?;
+        if !rem.is_empty() {
+            Err(sd(Failure::at(rem, "too much input")))
+        } else {
+            Ok(r)
+        }
+    }
+
+    /**
+    Convert into an element that doesn't borrow from its input.
+    */
+    pub fn into_owned(self) -> StructuredDataElement<'static> {
+        StructuredDataElement {
+            id: Cow::Owned(self.id.into_owned()),
+            params: self
+                .params
+                .into_iter()
+                .map(|(k, v)| (Cow::Owned(k.into_owned()), v))
+                .collect(),
+        }
+    }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Message<'a> {
     pub priority: Priority,
     pub timestamp: Option<DateTime<Utc>>,
-    pub hostname: Option<&'a str>,
-    pub app_name: Option<&'a str>,
-    pub proc_id: Option<&'a str>,
-    pub message_id: Option<&'a str>,
+    pub hostname: Option<Cow<'a, str>>,
+    pub app_name: Option<Cow<'a, str>>,
+    pub proc_id: Option<Cow<'a, str>>,
+    pub message_id: Option<Cow<'a, str>>,
     pub structured_data: Option<Vec<StructuredDataElement<'a>>>,
     pub message: Option<Cow<'a, str>>,
 }
 
 impl<'a> Message<'a> {
+    #[cfg(feature = "std")]
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &'a str) -> Self {
         Self::from_bytes(s.as_bytes())
     }
 
+    /**
+    Convert into a message that doesn't borrow from its input.
+
+    The owned message can be sent to other tasks or stored after the buffer
+    it was parsed from has been reused.
+    */
+    pub fn into_owned(self) -> Message<'static> {
+        fn owned(v: Option<Cow<str>>) -> Option<Cow<'static, str>> {
+            v.map(|v| Cow::Owned(v.into_owned()))
+        }
+
+        Message {
+            priority: self.priority,
+            timestamp: self.timestamp,
+            hostname: owned(self.hostname),
+            app_name: owned(self.app_name),
+            proc_id: owned(self.proc_id),
+            message_id: owned(self.message_id),
+            structured_data: self
+                .structured_data
+                .map(|sd| sd.into_iter().map(StructuredDataElement::into_owned).collect()),
+            message: owned(self.message),
+        }
+    }
+
+    #[cfg(feature = "std")]
     pub fn from_bytes(s: &'a [u8]) -> Self {
         Self::from_rfc5424_bytes(s).unwrap_or_else(|_| Self::from_rfc3164_bytes(s, &Utc::now()))
     }
 
     // RFC3164 format: <PRIVAL>TIMESTAMP HOSTNAME TAG: (MSG)
     // We treat the tag as part of the message.
+    #[cfg(feature = "std")]
     pub fn from_rfc3164_bytes(msg: &'a [u8], now: &DateTime<Utc>) -> Self {
         let mut unparsed = msg;
         let mut result = Message {
@@ -120,17 +185,17 @@ impl<'a> Message<'a> {
                     unparsed = rem;
 
                     if let Ok((hostname, rem)) = parsers::header_item(unparsed, "hostname") {
-                        result.hostname = hostname;
+                        result.hostname = hostname.map(Cow::Borrowed);
                         unparsed = rem;
                     }
                 }
             }
         }
 
-        result.message = if unparsed.len() > 0 { Some(String::from_utf8_lossy(unparsed)) } else { None };
+        result.message = if !unparsed.is_empty() { Some(String::from_utf8_lossy(unparsed)) } else { None };
 
         if result.timestamp.is_none() {
-            result.timestamp = Some(now.clone())
+            result.timestamp = Some(*now)
         }
 
         result
@@ -138,7 +203,10 @@ impl<'a> Message<'a> {
 
     // RFC5424 format: <PRIVAL>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA (MSG)
     pub fn from_rfc5424_bytes(msg: &'a [u8]) -> Result<Self, Error> {
-        let (priority, rem) = parsers::priority(msg)?;
+        let header = |err: Failure| err.into_error(msg, Section::Header);
+        let sd = |err: Failure| err.into_error(msg, Section::StructuredData);
+
+        let (priority, rem) = parsers::priority(msg).map_err(header)?;
 
         let mut result = Message {
             priority: Priority::from_raw(priority),
@@ -151,11 +219,12 @@ impl<'a> Message<'a> {
             message: None,
         };
 
-        let (version_item, rem) = parsers::header_item(rem, "version")?;
+        let (version_item, version_rem) = parsers::header_item(rem, "version").map_err(header)?;
         match version_item {
             Some("1") => (),
-            _ => return Err(err_msg("invalid message, version not 1"))
+            _ => return Err(header(Failure::at(rem, "version not 1")))
         };
+        let rem = version_rem;
 
         let ts_rem;
         let ts_attempt = parsers::iso8601_timestamp(rem);
@@ -164,37 +233,41 @@ impl<'a> Message<'a> {
             ts_rem = rem;
         } else {
             let err = ts_attempt.unwrap_err();
-            let (_, nil_rem) = parsers::byte(rem, b'-').map_err(move |_| err)?;
+            let (_, nil_rem) = parsers::byte(rem, b'-').map_err(move |_| header(err))?;
             ts_rem = nil_rem;
         }
 
-        let (_, rem) = parsers::byte(ts_rem, b' ')?;
+        let (_, rem) = parsers::byte(ts_rem, b' ').map_err(header)?;
 
-        let (hostname, rem) = parsers::header_item(rem, "hostname")?;
-        result.hostname = hostname;
+        let (hostname, rem) = parsers::header_item(rem, "hostname").map_err(header)?;
+        result.hostname = hostname.map(Cow::Borrowed);
 
-        let (app_name, rem) = parsers::header_item(rem, "app_name")?;
-        result.app_name = app_name;
+        let (app_name, rem) = parsers::header_item(rem, "app_name").map_err(header)?;
+        result.app_name = app_name.map(Cow::Borrowed);
 
-        let (proc_id, rem) = parsers::header_item(rem, "proc_id")?;
-        result.proc_id = proc_id;
+        let (proc_id, rem) = parsers::header_item(rem, "proc_id").map_err(header)?;
+        result.proc_id = proc_id.map(Cow::Borrowed);
 
-        let (message_id, mut rem) = parsers::header_item(rem, "message_id")?;
-        result.message_id = message_id;
+        let (message_id, mut rem) = parsers::header_item(rem, "message_id").map_err(header)?;
+        result.message_id = message_id.map(Cow::Borrowed);
 
         let mut maybe_sd = parsers::structured_data_element(rem);
-        if maybe_sd.is_ok() {
-            while let Ok((sde, sd_rem)) = maybe_sd {
-                match result.structured_data {
-                    None => result.structured_data = Some(vec![sde]),
-                    Some(ref mut sd) => sd.push(sde)
+        match maybe_sd {
+            Ok(_) => {
+                while let Ok((sde, sd_rem)) = maybe_sd {
+                    match result.structured_data {
+                        None => result.structured_data = Some(vec![sde]),
+                        Some(ref mut sd) => sd.push(sde)
+                    }
+                    rem = sd_rem;
+                    maybe_sd = parsers::structured_data_element(rem);
                 }
+            }
+            // If the structured data isn't nil then report why the first element is invalid
+            Err(err) => {
+                let (_, sd_rem) = parsers::byte(rem, b'-').map_err(move |_| sd(err))?;
                 rem = sd_rem;
-                maybe_sd = parsers::structured_data_element(rem);
             }
-        } else {
-            let (_, sd_rem) = parsers::byte(rem, b'-')?;
-            rem = sd_rem;
         }
 
         if let Ok((_, rem)) = parsers::byte(rem, b' ') {
@@ -206,8 +279,10 @@ impl<'a> Message<'a> {
             }
 
             result.message = if is_utf8 {
-                let trimmed = std::str::from_utf8(message_bytes)?.trim();
-                if trimmed.len() > 0 {
+                let trimmed = parsers::utf8(message_bytes, message_bytes.len())
+                    .map_err(header)?
+                    .trim();
+                if !trimmed.is_empty() {
                     Some(Cow::Borrowed(trimmed))
                 } else {
                     None
@@ -215,7 +290,7 @@ impl<'a> Message<'a> {
             } else {
                 let owned = String::from_utf8_lossy(message_bytes);
                 let trimmed = owned.trim();
-                if trimmed.len() > 0 {
+                if !trimmed.is_empty() {
                     Some(Cow::Owned(trimmed.to_owned()))
                 } else {
                     None
@@ -231,17 +306,13 @@ impl<'a> Message<'a> {
 mod tests {
     use super::*;
     use chrono::{Datelike, TimeZone};
-    use crate::test_util::to_timestamp;
+    use crate::test_util::{rfc5424_message, to_rfc5424, to_timestamp};
+    use proptest::prelude::*;
     use std::borrow::Cow::Borrowed;
 
     impl<'a> StructuredDataElement<'a> {
         fn from_str(s: &'a str) -> Result<Self, Error> {
-            let (r, rem) = parsers::structured_data_element(s.as_bytes())?;
-            if rem.len() > 0 {
-                Err(err_msg("too much input"))
-            } else {
-                Ok(r)
-            }
+            Self::from_bytes(s.as_bytes())
         }
     }
 
@@ -256,10 +327,10 @@ mod tests {
                 severity: 6,
             },
             timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
-            hostname: Some("docker-desktop"),
-            app_name: Some("8b1089798cf8"),
-            proc_id: Some("1481"),
-            message_id: Some("8b1089798cf8"),
+            hostname: Some(Borrowed("docker-desktop")),
+            app_name: Some(Borrowed("8b1089798cf8")),
+            proc_id: Some(Borrowed("1481")),
+            message_id: Some(Borrowed("8b1089798cf8")),
             structured_data: None,
             message: Some(Borrowed("hello world")),
         };
@@ -275,7 +346,44 @@ mod tests {
 
         let actual = Message::from_rfc5424_bytes(input);
 
-        assert_eq!("missing hostname", actual.unwrap_err().to_string());
+        let err = actual.unwrap_err();
+        assert_eq!("invalid header at byte 27: missing hostname", err.to_string());
+        assert!(matches!(err, Error::Header { offset: 27, .. }));
+    }
+
+    #[test]
+    fn parse_rfc5424_errors_locate_the_failure() {
+        fn kind(err: &Error) -> &'static str {
+            match err {
+                Error::Header { .. } => "header",
+                Error::StructuredData { .. } => "structured data",
+                Error::Encoding { .. } => "encoding",
+                _ => "other",
+            }
+        }
+
+        let cases: &[(&[u8], &str, usize)] = &[
+            (b"<300>1 - - - - - -", "header", 0),
+            (b"<30>2 - - - - - -", "header", 4),
+            (b"<30>1 2020-02-13T00:51:39 - - - - -", "header", 6),
+            (b"<30>1 - - - - - [id a=\"1\" b]", "structured data", 27),
+            (b"<30>1 - - - - - [id a=\"\xFF\"]", "encoding", 23),
+            (b"<30>1 - - - - - - \xEF\xBB\xBFhello \xFF", "encoding", 27),
+        ];
+
+        for (input, expected_kind, offset) in cases {
+            let err = Message::from_rfc5424_bytes(input).expect_err("should fail");
+
+            assert_eq!(*expected_kind, kind(&err), "{:?}: {}", String::from_utf8_lossy(input), err);
+            assert_eq!(Some(*offset), err.offset(), "{:?}: {}", String::from_utf8_lossy(input), err);
+        }
+    }
+
+    #[test]
+    fn structured_data_errors_locate_the_failure() {
+        let err = StructuredDataElement::from_str("[id a=\"1\"] extra").expect_err("should fail");
+
+        assert!(matches!(err, Error::StructuredData { offset: 10, .. }), "{}", err);
     }
 
     #[test]
@@ -289,10 +397,10 @@ mod tests {
                 severity: 2,
             },
             timestamp: to_timestamp("2003-10-11T22:14:15.003Z"),
-            hostname: Some("mymachine.example.com"),
-            app_name: Some("su"),
+            hostname: Some(Borrowed("mymachine.example.com")),
+            app_name: Some(Borrowed("su")),
             proc_id: None,
-            message_id: Some("ID47"),
+            message_id: Some(Borrowed("ID47")),
             structured_data: None,
             message: Some(Borrowed("’su root’ failed for lonvick on /dev/pts/8")),
         };
@@ -313,9 +421,9 @@ mod tests {
                 severity: 5,
             },
             timestamp: to_timestamp("2003-08-24T05:14:15.000003-07:00"),
-            hostname: Some("192.0.2.1"),
-            app_name: Some("myproc"),
-            proc_id: Some("8710"),
+            hostname: Some(Borrowed("192.0.2.1")),
+            app_name: Some(Borrowed("myproc")),
+            proc_id: Some(Borrowed("8710")),
             message_id: None,
             structured_data: None,
             message: Some(Borrowed("%% It's time to make the do-nuts.")),
@@ -331,10 +439,11 @@ mod tests {
         // example 3 from https://tools.ietf.org/html/rfc5424
         let input = b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"] \xEF\xBB\xBFAn application event log entry...\n";
 
-        let mut sd_params = vec![];
-        sd_params.push(("iut", "3".to_owned()));
-        sd_params.push(("eventSource", "Application".to_owned()));
-        sd_params.push(("eventID", "1011".to_owned()));
+        let sd_params = vec![
+            (Borrowed("iut"), "3".to_owned()),
+            (Borrowed("eventSource"), "Application".to_owned()),
+            (Borrowed("eventID"), "1011".to_owned()),
+        ];
 
         let expected = Message {
             priority: Priority {
@@ -342,12 +451,12 @@ mod tests {
                 severity: 5,
             },
             timestamp: to_timestamp("2003-10-11T22:14:15.003Z"),
-            hostname: Some("mymachine.example.com"),
-            app_name: Some("evntslog"),
+            hostname: Some(Borrowed("mymachine.example.com")),
+            app_name: Some(Borrowed("evntslog")),
             proc_id: None,
-            message_id: Some("ID47"),
+            message_id: Some(Borrowed("ID47")),
             structured_data: Some(vec![StructuredDataElement {
-                id: "exampleSDID@32473",
+                id: Borrowed("exampleSDID@32473"),
                 params: sd_params,
             }]),
             message: Some(Borrowed("An application event log entry...")),
@@ -364,21 +473,23 @@ mod tests {
 
         let input = b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"][examplePriority@32473 class=\"high\"]";
 
-        let mut sd_params = vec![];
-        sd_params.push(("iut", "3".to_owned()));
-        sd_params.push(("eventSource", "Application".to_owned()));
-        sd_params.push(("eventID", "1011".to_owned()));
+        let sd_params = vec![
+            (Borrowed("iut"), "3".to_owned()),
+            (Borrowed("eventSource"), "Application".to_owned()),
+            (Borrowed("eventID"), "1011".to_owned()),
+        ];
 
-        let mut sd_params2 = vec![];
-        sd_params2.push(("class", "high".to_owned()));
+        let sd_params2 = vec![
+            (Borrowed("class"), "high".to_owned()),
+        ];
 
         let sd = vec![
             StructuredDataElement {
-                id: "exampleSDID@32473",
+                id: Borrowed("exampleSDID@32473"),
                 params: sd_params,
             },
             StructuredDataElement {
-                id: "examplePriority@32473",
+                id: Borrowed("examplePriority@32473"),
                 params: sd_params2,
             },
         ];
@@ -389,10 +500,10 @@ mod tests {
                 severity: 5,
             },
             timestamp: to_timestamp("2003-10-11T22:14:15.003Z"),
-            hostname: Some("mymachine.example.com"),
-            app_name: Some("evntslog"),
+            hostname: Some(Borrowed("mymachine.example.com")),
+            app_name: Some(Borrowed("evntslog")),
             proc_id: None,
-            message_id: Some("ID47"),
+            message_id: Some(Borrowed("ID47")),
             structured_data: Some(sd),
             message: None,
         };
@@ -429,13 +540,14 @@ mod tests {
     fn structured_data_param_from_string() {
         let input = "[exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"]";
 
-        let mut sd_params = vec![];
-        sd_params.push(("iut", "3".to_owned()));
-        sd_params.push(("eventSource", "Application".to_owned()));
-        sd_params.push(("eventID", "1011".to_owned()));
+        let sd_params = vec![
+            (Borrowed("iut"), "3".to_owned()),
+            (Borrowed("eventSource"), "Application".to_owned()),
+            (Borrowed("eventID"), "1011".to_owned()),
+        ];
 
         let expected = StructuredDataElement {
-            id: "exampleSDID@32473",
+            id: Borrowed("exampleSDID@32473"),
             params: sd_params,
         };
 
@@ -455,7 +567,7 @@ mod tests {
         assert_eq!(msg.priority.facility, 4);
         assert_eq!(msg.priority.severity, 2);
         assert_eq!(msg.timestamp.unwrap().month(), 10); // Rest depends on local timezone ":-)
-        assert_eq!(msg.hostname, Some("mymachine"));
+        assert_eq!(msg.hostname.as_deref(), Some("mymachine"));
 
         // The 'tag' remains in the message; although we could extract 'su' as the tag, adherence to
         // this format seems very patchy, and we're more likely to end up breaking messages that
@@ -471,4 +583,61 @@ mod tests {
 
         assert_eq!("Use the BFG!", msg.message.unwrap());
     }
+
+    #[test]
+    fn owned_message_outlives_input() {
+        let msg = {
+            let input = b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\"] An application event log entry...".to_vec();
+
+            Message::from_bytes(&input).into_owned()
+        };
+
+        assert_eq!(Some("mymachine.example.com"), msg.hostname.as_deref());
+        assert_eq!("exampleSDID@32473", msg.structured_data.unwrap()[0].id);
+        assert_eq!(Some("An application event log entry..."), msg.message.as_deref());
+    }
+
+    #[test]
+    fn message_json_roundtrip() {
+        let input = b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\"] An application event log entry...";
+
+        let expected = serde_json::json!({
+            "priority": { "facility": 20, "severity": 5 },
+            "timestamp": "2003-10-11T22:14:15.003Z",
+            "hostname": "mymachine.example.com",
+            "app_name": "evntslog",
+            "proc_id": null,
+            "message_id": "ID47",
+            "structured_data": [{
+                "id": "exampleSDID@32473",
+                "params": [["iut", "3"], ["eventSource", "Application"]],
+            }],
+            "message": "An application event log entry...",
+        });
+
+        let msg = Message::from_rfc5424_bytes(input).expect("could not parse input for syslog");
+
+        let json = serde_json::to_value(&msg).expect("could not serialize message");
+        assert_eq!(expected, json);
+
+        let roundtripped: Message = serde_json::from_value(json).expect("could not deserialize message");
+        assert_eq!(msg, roundtripped);
+    }
+
+    proptest! {
+        #[test]
+        fn parse_arbitrary_bytes_does_not_panic(input in proptest::collection::vec(any::<u8>(), 0..512)) {
+            let _ = Message::from_rfc5424_bytes(&input);
+            let _ = Message::from_bytes(&input).into_clef();
+        }
+
+        #[test]
+        fn parse_rfc5424_roundtrip(expected in rfc5424_message()) {
+            let input = to_rfc5424(&expected);
+
+            let actual = Message::from_rfc5424_bytes(&input).expect("could not parse generated message");
+
+            prop_assert_eq!(expected, actual.into_owned());
+        }
+    }
 }
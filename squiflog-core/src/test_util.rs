@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use proptest::{collection::vec, option, prelude::*};
+
+use crate::syslog::{Message, Priority, StructuredDataElement};
+
+pub fn to_timestamp(iso8601: &str) -> Option<DateTime<Utc>> {
+    Some(DateTime::parse_from_rfc3339(iso8601).expect("invalid test timestamp").with_timezone(&Utc))
+}
+
+/**
+Format a message using the RFC 5424 format.
+*/
+pub fn to_rfc5424(msg: &Message) -> Vec<u8> {
+    fn nil(v: &Option<Cow<str>>) -> String {
+        v.as_deref().unwrap_or("-").to_owned()
+    }
+
+    let mut buf = format!(
+        "<{}>1 {} {} {} {} {} ",
+        msg.priority.facility * 8 + msg.priority.severity,
+        msg.timestamp
+            .map(|ts| ts.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            .unwrap_or_else(|| "-".to_owned()),
+        nil(&msg.hostname),
+        nil(&msg.app_name),
+        nil(&msg.proc_id),
+        nil(&msg.message_id),
+    );
+
+    match msg.structured_data {
+        Some(ref sd) => {
+            for element in sd {
+                buf.push('[');
+                buf.push_str(&element.id);
+                for (name, value) in &element.params {
+                    let value = value
+                        .replace('\\', "\\\\")
+                        .replace('"', "\\\"")
+                        .replace(']', "\\]");
+
+                    buf.push_str(&format!(" {}=\"{}\"", name, value));
+                }
+                buf.push(']');
+            }
+        }
+        None => buf.push('-'),
+    }
+
+    let mut buf = buf.into_bytes();
+    if let Some(ref message) = msg.message {
+        buf.extend_from_slice(b" \xEF\xBB\xBF");
+        buf.extend_from_slice(message.as_bytes());
+    }
+
+    buf
+}
+
+fn header_item(max_len: usize) -> impl Strategy<Value = Option<Cow<'static, str>>> {
+    option::of(
+        proptest::string::string_regex(&format!("[!-~]{{1,{}}}", max_len))
+            .unwrap()
+            .prop_filter("`-` is nil", |v| v != "-")
+            .prop_map(Cow::Owned),
+    )
+}
+
+fn sd_name() -> impl Strategy<Value = Cow<'static, str>> {
+    "[!#-<>-\\\\^-~]{1,32}".prop_map(Cow::Owned)
+}
+
+fn sd_element() -> impl Strategy<Value = StructuredDataElement<'static>> {
+    (sd_name(), vec((sd_name(), "[ -~\u{e9}\u{2019}]{0,16}"), 0..4))
+        .prop_map(|(id, params)| StructuredDataElement { id, params })
+}
+
+/**
+Generate valid RFC 5424 messages.
+*/
+pub fn rfc5424_message() -> impl Strategy<Value = Message<'static>> {
+    (
+        (0u8..24, 0u8..8),
+        option::of((0i64..4_102_444_800, 0u32..1_000_000)),
+        (header_item(255), header_item(48), header_item(128), header_item(32)),
+        option::of(vec(sd_element(), 1..4)),
+        option::of("[!-~\u{e9}\u{2019}]([ -~\u{e9}\u{2019}]{0,64}[!-~])?".prop_map(Cow::Owned)),
+    )
+        .prop_map(
+            |((facility, severity), timestamp, (hostname, app_name, proc_id, message_id), structured_data, message)| {
+                Message {
+                    priority: Priority { facility, severity },
+                    timestamp: timestamp.map(|(secs, micros)| Utc.timestamp(secs, micros * 1000)),
+                    hostname,
+                    app_name,
+                    proc_id,
+                    message_id,
+                    structured_data,
+                    message,
+                }
+            },
+        )
+}
@@ -0,0 +1,39 @@
+/*
+ * Convert SYSLOG messages into CLEF.
+ *
+ * Link against the `squiflog_ffi` library built from the `squiflog-ffi` crate.
+ */
+
+#ifndef SQUIFLOG_H
+#define SQUIFLOG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Parse a SYSLOG message and convert it into a CLEF-encoded JSON object.
+ *
+ * `bytes` points to `len` bytes of a single SYSLOG message. It doesn't need to be
+ * null-terminated, and isn't retained after the call returns.
+ *
+ * Returns a null-terminated UTF-8 string that must be freed with `squiflog_free`,
+ * or `NULL` if the message couldn't be converted.
+ */
+char *squiflog_parse(const uint8_t *bytes, size_t len);
+
+/*
+ * Free a string returned by `squiflog_parse`.
+ *
+ * Passing `NULL` is a no-op.
+ */
+void squiflog_free(char *json);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,98 @@
+/*!
+A C ABI for converting SYSLOG messages into CLEF.
+
+See `include/squiflog.h` for the declarations to use from C.
+*/
+
+use std::{
+    ffi::CString,
+    os::raw::c_char,
+    panic::catch_unwind,
+    ptr,
+    slice,
+};
+
+use squiflog_core::syslog;
+
+/**
+Parse a SYSLOG message and convert it into a CLEF-encoded JSON object.
+
+The returned string must be freed with `squiflog_free`. A null pointer is
+returned if the message couldn't be converted.
+
+# Safety
+
+`bytes` must point to `len` readable bytes, or be null if `len` is `0`.
+*/
+#[no_mangle]
+pub unsafe extern "C" fn squiflog_parse(bytes: *const u8, len: usize) -> *mut c_char {
+    let msg: &[u8] = if len == 0 {
+        &[]
+    } else if bytes.is_null() {
+        return ptr::null_mut();
+    } else {
+        slice::from_raw_parts(bytes, len)
+    };
+
+    // Panics must not unwind across the FFI boundary
+    catch_unwind(|| {
+        let clef = syslog::Message::from_bytes(msg).into_clef();
+        let json = serde_json::to_vec(&clef).ok()?;
+
+        // JSON strings escape any nul bytes, so this won't fail
+        CString::new(json).ok()
+    })
+    .ok()
+    .flatten()
+    .map(CString::into_raw)
+    .unwrap_or_else(ptr::null_mut)
+}
+
+/**
+Free a string returned by `squiflog_parse`.
+
+# Safety
+
+`json` must be null, or a pointer returned by `squiflog_parse` that hasn't already been freed.
+*/
+#[no_mangle]
+pub unsafe extern "C" fn squiflog_free(json: *mut c_char) {
+    if !json.is_null() {
+        drop(CString::from_raw(json));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn parse_returns_clef_json() {
+        let input = b"<30>1 2020-02-13T00:51:39.527825Z docker-desktop app 1481 - - hello world";
+
+        unsafe {
+            let json = squiflog_parse(input.as_ptr(), input.len());
+            assert!(!json.is_null());
+
+            let clef: serde_json::Value = serde_json::from_slice(CStr::from_ptr(json).to_bytes())
+                .expect("invalid JSON");
+
+            assert_eq!("hello world", clef["@m"]);
+            assert_eq!("docker-desktop", clef["hostname"]);
+
+            squiflog_free(json);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_null_input() {
+        unsafe {
+            assert!(squiflog_parse(ptr::null(), 1).is_null());
+
+            let json = squiflog_parse(ptr::null(), 0);
+            assert!(!json.is_null());
+            squiflog_free(json);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+/*!
+Python bindings for converting SYSLOG messages into CLEF.
+
+Build and install into the current Python environment with [`maturin`](https://www.maturin.rs):
+
+```text
+cd squiflog-py
+maturin develop --release
+```
+
+Messages can be given as either `bytes` or `str`:
+
+```text
+>>> import squiflog
+>>> squiflog.to_clef(b"<30>1 2020-02-13T00:51:39Z docker-desktop app 1481 - - hello world")
+{'@t': '2020-02-13T00:51:39Z', '@m': 'hello world', '@l': 'info', ...}
+```
+*/
+
+use pyo3::{
+    create_exception,
+    exceptions::PyValueError,
+    prelude::*,
+    types::PyBytes,
+};
+
+use serde::Serialize;
+
+use squiflog_core::{
+    syslog,
+    Error,
+};
+
+create_exception!(
+    squiflog,
+    ParseError,
+    PyValueError,
+    "A SYSLOG message couldn't be parsed. The arguments are the error message and the byte offset where parsing failed."
+);
+
+/**
+Parse a SYSLOG message into a `dict` of its fields.
+
+If `rfc5424` is true then only RFC 5424 messages are accepted. Otherwise,
+messages that aren't valid RFC 5424 fall back to a lenient RFC 3164 parser.
+*/
+#[pyfunction]
+#[pyo3(signature = (msg, rfc5424 = false))]
+fn parse(py: Python<'_>, msg: &Bound<'_, PyAny>, rfc5424: bool) -> PyResult<Py<PyAny>> {
+    with_bytes(msg, |msg| to_py(py, &from_bytes(msg, rfc5424)?))
+}
+
+/**
+Parse a SYSLOG message and convert it into a CLEF event `dict`.
+*/
+#[pyfunction]
+#[pyo3(signature = (msg, rfc5424 = false))]
+fn to_clef(py: Python<'_>, msg: &Bound<'_, PyAny>, rfc5424: bool) -> PyResult<Py<PyAny>> {
+    with_bytes(msg, |msg| to_py(py, &from_bytes(msg, rfc5424)?.into_clef()))
+}
+
+/**
+Parse a SYSLOG message and convert it into a CLEF-encoded JSON object.
+
+The result can be written as a line to a CLEF file.
+*/
+#[pyfunction]
+#[pyo3(signature = (msg, rfc5424 = false))]
+fn to_clef_json(msg: &Bound<'_, PyAny>, rfc5424: bool) -> PyResult<String> {
+    with_bytes(msg, |msg| to_json(&from_bytes(msg, rfc5424)?.into_clef()))
+}
+
+#[pymodule]
+fn squiflog(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ParseError", m.py().get_type::<ParseError>())?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(to_clef, m)?)?;
+    m.add_function(wrap_pyfunction!(to_clef_json, m)?)?;
+
+    Ok(())
+}
+
+// Messages are read from `bytes` as-is, or from the UTF-8 encoding of a `str`
+fn with_bytes<T>(msg: &Bound<'_, PyAny>, f: impl FnOnce(&[u8]) -> PyResult<T>) -> PyResult<T> {
+    match msg.cast::<PyBytes>() {
+        Ok(bytes) => f(bytes.as_bytes()),
+        Err(_) => {
+            let msg: String = msg.extract()?;
+            f(msg.as_bytes())
+        }
+    }
+}
+
+fn from_bytes(msg: &[u8], rfc5424: bool) -> PyResult<syslog::Message<'_>> {
+    if rfc5424 {
+        syslog::Message::from_rfc5424_bytes(msg).map_err(parse_error)
+    } else {
+        Ok(syslog::Message::from_bytes(msg))
+    }
+}
+
+fn to_json(value: &impl Serialize) -> PyResult<String> {
+    serde_json::to_string(value).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+// Values go through JSON so they match the CLEF written by `squiflog` exactly
+fn to_py(py: Python<'_>, value: &impl Serialize) -> PyResult<Py<PyAny>> {
+    let json = to_json(value)?;
+
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+fn parse_error(err: Error) -> PyErr {
+    ParseError::new_err((err.to_string(), err.offset()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_module(f: impl FnOnce(&Bound<'_, PyModule>)) {
+        Python::initialize();
+        Python::attach(|py| {
+            let m = PyModule::new(py, "squiflog").expect("failed to create module");
+            squiflog(&m).expect("failed to initialize module");
+
+            f(&m)
+        })
+    }
+
+    #[test]
+    fn to_clef_returns_dict() {
+        with_module(|m| {
+            let clef = m
+                .getattr("to_clef")
+                .and_then(|f| f.call1(("<30>1 2020-02-13T00:51:39Z docker-desktop app 1481 - - hello world",)))
+                .expect("failed to convert message");
+
+            let msg: String = clef.get_item("@m").and_then(|v| v.extract()).expect("missing message");
+            assert_eq!("hello world", msg);
+        })
+    }
+
+    #[test]
+    fn rfc5424_errors_raise_parse_error() {
+        with_module(|m| {
+            let err = m
+                .getattr("parse")
+                .and_then(|f| f.call1((&b"<30>1 2020-02-13T00:51:39Z "[..], true)))
+                .expect_err("should fail");
+
+            assert!(err.is_instance_of::<ParseError>(m.py()));
+
+            let (_, offset): (String, Option<usize>) = err
+                .value(m.py())
+                .getattr("args")
+                .and_then(|args| args.extract())
+                .expect("missing args");
+            assert_eq!(Some(27), offset);
+        })
+    }
+}
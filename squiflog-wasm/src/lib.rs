@@ -0,0 +1,76 @@
+/*!
+JavaScript bindings for converting SYSLOG messages into CLEF.
+
+Build with [`wasm-pack`](https://rustwasm.github.io/wasm-pack/):
+
+```text
+wasm-pack build squiflog-wasm --target web
+```
+*/
+
+use wasm_bindgen::prelude::*;
+
+use squiflog_core::{
+    syslog,
+    Error,
+};
+
+/**
+Parse a SYSLOG message and convert it into a CLEF-encoded JSON object.
+
+Messages that aren't valid RFC 5424 fall back to a lenient RFC 3164 parser.
+*/
+#[wasm_bindgen(js_name = toClef)]
+pub fn to_clef(msg: &str) -> Result<String, JsError> {
+    to_json(syslog::Message::from_str(msg)).map_err(js_error)
+}
+
+/**
+Parse an RFC 5424 SYSLOG message and convert it into a CLEF-encoded JSON object.
+
+The error thrown for an invalid message includes the byte offset where parsing failed.
+*/
+#[wasm_bindgen(js_name = rfc5424ToClef)]
+pub fn rfc5424_to_clef(msg: &str) -> Result<String, JsError> {
+    syslog::Message::from_rfc5424_bytes(msg.as_bytes())
+        .and_then(to_json)
+        .map_err(js_error)
+}
+
+fn to_json(msg: syslog::Message) -> Result<String, Error> {
+    Ok(serde_json::to_string(&msg.into_clef())?)
+}
+
+fn js_error(err: Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn to_clef_returns_json() {
+        let json = to_clef("<30>1 2020-02-13T00:51:39.527825Z docker-desktop app 1481 - - hello world")
+            .unwrap_or_else(|_| panic!("failed to convert message"));
+
+        let clef: Value = serde_json::from_str(&json).expect("invalid JSON");
+
+        assert_eq!("hello world", clef["@m"]);
+        assert_eq!("docker-desktop", clef["hostname"]);
+    }
+
+    #[test]
+    fn rfc5424_to_clef_matches_to_clef() {
+        let msg = "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\"] An application event log entry...";
+
+        let lenient = to_clef(msg).unwrap_or_else(|_| panic!("failed to convert message"));
+        let strict = rfc5424_to_clef(msg).unwrap_or_else(|_| panic!("failed to convert message"));
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&lenient).expect("invalid JSON"),
+            serde_json::from_str::<Value>(&strict).expect("invalid JSON"),
+        );
+    }
+}
@@ -0,0 +1,56 @@
+/*!
+Generates man pages and shell completions for the command-line interface.
+
+They're written to `$OUT_DIR`, or to `$SQUIFLOG_GEN_DIR` if it's set,
+so packagers can ship them alongside the binary:
+
+```shell
+$ SQUIFLOG_GEN_DIR=target/gen cargo build --release --bin squiflog
+$ ls target/gen/man target/gen/completions
+```
+*/
+
+use std::{
+    env,
+    fs,
+    io,
+    path::PathBuf,
+};
+
+use clap::{
+    CommandFactory,
+    ValueEnum,
+};
+
+use clap_complete::Shell;
+
+#[allow(dead_code)]
+mod args {
+    include!("src/cli/args.rs");
+}
+
+fn main() -> io::Result<()> {
+    println!("cargo:rerun-if-changed=src/cli/args.rs");
+    println!("cargo:rerun-if-env-changed=SQUIFLOG_GEN_DIR");
+
+    // `squiflog update` downloads the binary built for the same target
+    println!("cargo:rustc-env=SQUIFLOG_TARGET={}", env::var("TARGET").expect("missing TARGET"));
+
+    let out_dir = match env::var_os("SQUIFLOG_GEN_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("OUT_DIR").expect("missing OUT_DIR")),
+    };
+
+    // One page for the command, and one for each subcommand, like `squiflog-tail.1`
+    let man_dir = out_dir.join("man");
+    fs::create_dir_all(&man_dir)?;
+    clap_mangen::generate_to(args::Cli::command(), &man_dir)?;
+
+    let completions_dir = out_dir.join("completions");
+    fs::create_dir_all(&completions_dir)?;
+    for shell in Shell::value_variants() {
+        clap_complete::generate_to(*shell, &mut args::Cli::command(), "squiflog", &completions_dir)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+/*!
+Sign a release binary so `squiflog update` will install it.
+
+The signing key is a hex-encoded Ed25519 secret key in `SQUIFLOG_UPDATE_SIGNING_KEY`.
+The signature is written alongside the binary with a `.sig` extension:
+
+```shell
+$ cargo run --example sign-release -- publish/squiflog-x86_64-unknown-linux-musl 1.0.123 x86_64-unknown-linux-musl
+```
+
+Use `--public-key` to print the public key that releases need to be built with,
+in `SQUIFLOG_UPDATE_PUBLIC_KEY`. If that variable is set when signing, it must
+match the signing key.
+*/
+
+use std::{
+    env,
+    fs,
+    process,
+};
+
+use ed25519_dalek::{
+    Signer,
+    SigningKey,
+};
+
+use semver::Version;
+
+#[allow(dead_code)]
+mod signing {
+    include!("../src/cli/update/signing.rs");
+}
+
+fn main() {
+    if let Err(err) = run(&env::args().skip(1).collect::<Vec<_>>()) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let secret = env::var("SQUIFLOG_UPDATE_SIGNING_KEY").map_err(|_| "`SQUIFLOG_UPDATE_SIGNING_KEY` isn't set")?;
+    let key = signing::parse_key(&secret)
+        .map(|secret| SigningKey::from_bytes(&secret))
+        .ok_or("invalid signing key, expected 64 hex characters")?;
+
+    let public_key = key
+        .verifying_key()
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    // Catch releases that would be built with a key that can't verify them
+    if let Ok(expected) = env::var("SQUIFLOG_UPDATE_PUBLIC_KEY") {
+        if !expected.trim().eq_ignore_ascii_case(&public_key) {
+            return Err("`SQUIFLOG_UPDATE_PUBLIC_KEY` isn't the public key for `SQUIFLOG_UPDATE_SIGNING_KEY`".into());
+        }
+    }
+
+    match args {
+        [flag] if flag == "--public-key" => {
+            println!("{}", public_key);
+
+            Ok(())
+        }
+        [binary, version, target] => {
+            // Signed the same way `squiflog update` formats the release's tag
+            let version = Version::parse(version.strip_prefix('v').unwrap_or(version))
+                .map_err(|err| format!("invalid version `{}`: {}", version, err))?;
+
+            let bytes = fs::read(binary).map_err(|err| format!("failed to read `{}`: {}", binary, err))?;
+            let signature = key.sign(&signing::payload(&version.to_string(), target, &bytes));
+
+            let path = format!("{}.sig", binary);
+            fs::write(&path, signature.to_bytes()).map_err(|err| format!("failed to write `{}`: {}", path, err))?;
+
+            eprintln!("signed squiflog {} for {} into `{}`", version, target, path);
+
+            Ok(())
+        }
+        _ => Err("expected `<BINARY> <VERSION> <TARGET>`, or `--public-key`".into()),
+    }
+}
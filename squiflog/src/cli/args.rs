@@ -0,0 +1,419 @@
+use std::path::PathBuf;
+
+use clap::{
+    Args,
+    Parser,
+    Subcommand,
+    ValueEnum,
+};
+
+use clap_complete::Shell;
+
+/**
+Ingest SYSLOG messages and convert them into CLEF.
+
+Without a subcommand, squiflog runs the SYSLOG server configured from environment
+variables and writes CLEF events to stdout.
+*/
+#[derive(Debug, Parser)]
+#[command(name = "squiflog", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /**
+    Show CLEF events as they arrive, with colorized levels.
+
+    Events are read from a CLEF stream, like the output of the squiflog server,
+    or received directly as SYSLOG messages using `--listen`.
+    */
+    Tail(TailArgs),
+    /**
+    Convert files of archived SYSLOG messages into CLEF files.
+
+    Each file is converted into a CLEF file with the same name and a `.clef` extension.
+    Statistics about the messages in each file are written to stderr.
+    */
+    Convert(ConvertArgs),
+    /**
+    Generate a starter configuration file.
+
+    squiflog is configured using environment variables, so the file is an env file
+    that can be passed to `docker run --env-file` or sourced by a shell. Settings
+    that aren't needed for the chosen scenario are included but commented out.
+
+    Without `--scenario`, the settings are prompted for interactively.
+    */
+    Init(InitArgs),
+    /**
+    Collect a report for troubleshooting an input.
+
+    The report includes the version, platform, effective configuration with secrets
+    masked, whether the SYSLOG address can be bound, whether outputs are reachable,
+    and recent parse failures. Attach it to support tickets.
+    */
+    Doctor(DoctorArgs),
+    /**
+    Replay SYSLOG messages from a packet capture.
+
+    UDP datagrams in a pcap file are converted into CLEF and written to stdout,
+    as if the server had received them when they were captured.
+    */
+    Replay(ReplayArgs),
+    /**
+    Show how SYSLOG messages are converted using the current configuration.
+
+    For each message, the format it's parsed as, which enrichment rules
+    changed it, and the resulting CLEF event are written to stdout.
+    */
+    TestRules(TestRulesArgs),
+    /**
+    Generate a shell completion script.
+
+    The script is written to stdout. For bash, load it in `~/.bashrc` with
+    `source <(squiflog completions bash)`.
+    */
+    Completions(CompletionsArgs),
+    /**
+    Check that a stream of newline-delimited CLEF is valid.
+
+    Each event is checked for reified `@` properties with the right types, valid
+    timestamps, and duplicate property names. Problems are written to stdout, and
+    the command fails if any events are invalid.
+    */
+    ValidateClef(ValidateClefArgs),
+    /**
+    Show a live dashboard of the messages running servers are receiving.
+
+    Each server's throughput and received, processed, and dropped messages,
+    along with the busiest hostnames and apps and the reasons messages were
+    dropped, are redrawn in the terminal until interrupted with Ctrl+C. Servers
+    only serve these stats when `SYSLOG_STATS_ADDRESS` is set.
+    */
+    Dashboard(DashboardArgs),
+    /**
+    Cluster a corpus of SYSLOG messages into message templates.
+
+    Messages from each app are grouped by their text, with the parts that vary
+    replaced by `<*>`, using the Drain algorithm. Templates are written to stdout
+    with how many messages they matched, most common first.
+    */
+    ExtractTemplates(ExtractTemplatesArgs),
+    /**
+    Check for a newer release and replace this binary with it.
+
+    The release's binary is only installed if its signature can be verified
+    using the public key squiflog was built with.
+    */
+    Update(UpdateArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct TailArgs {
+    /**
+    A file of newline-delimited CLEF to read. Defaults to stdin.
+    */
+    #[arg(value_name = "FILE", conflicts_with = "listen")]
+    pub path: Option<String>,
+
+    /**
+    Keep reading the file as it grows.
+    */
+    #[arg(short, long, requires = "path")]
+    pub follow: bool,
+
+    /**
+    Receive SYSLOG messages on this address instead of reading CLEF, like `udp://0.0.0.0:514`.
+
+    The address is bound by `tail` itself, so it can't be one a running server
+    is already receiving on. Stop the server first, or pipe its output into
+    `tail` to watch it instead.
+    */
+    #[arg(short, long, value_name = "ADDRESS")]
+    pub listen: Option<String>,
+
+    /**
+    Only show events matching a filter. Filters are `name=value`, `name!=value`,
+    `name~text` (contains), or just `name` (has the property). Use `@l` for the
+    level and `@m` for the message. Events must match all filters.
+    */
+    #[arg(long = "filter", value_name = "FILTER")]
+    pub filters: Vec<String>,
+
+    /**
+    Don't colorize output. This is the default when stdout isn't a terminal,
+    or the `NO_COLOR` environment variable is set.
+    */
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /**
+    The files to convert. Glob patterns like `archive/**/*.log.gz` are expanded,
+    so quote them to avoid shell limits on the number of arguments. Files compressed
+    with gzip are decompressed.
+    */
+    #[arg(value_name = "PATTERN", required = true)]
+    pub patterns: Vec<String>,
+
+    /**
+    Write CLEF files into this directory instead of alongside each file.
+    */
+    #[arg(short, long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /**
+    Compress CLEF files using gzip.
+    */
+    #[arg(long)]
+    pub gzip: bool,
+
+    /**
+    Split messages using octet counting instead of newlines.
+    */
+    #[arg(long)]
+    pub octet_counted: bool,
+
+    /**
+    Replace CLEF files that already exist.
+    */
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /**
+    Where squiflog will be run. Prompted for if not given.
+    */
+    #[arg(short, long)]
+    pub scenario: Option<Scenario>,
+
+    /**
+    The address to receive SYSLOG messages on.
+    */
+    #[arg(short, long, value_name = "ADDRESS")]
+    pub listen: Option<String>,
+
+    /**
+    The address of the Seq server to forward events to.
+    */
+    #[arg(long, value_name = "URL")]
+    pub seq_address: Option<String>,
+
+    /**
+    The Seq API key to forward events with.
+    */
+    #[arg(long, value_name = "KEY")]
+    pub api_key: Option<String>,
+
+    /**
+    Enable diagnostic logs and metrics.
+    */
+    #[arg(long)]
+    pub diagnostics: bool,
+
+    /**
+    The file to write. Use `-` to write to stdout.
+    */
+    #[arg(short, long, value_name = "FILE", default_value = "squiflog.env")]
+    pub output: PathBuf,
+
+    /**
+    Replace the file if it already exists.
+    */
+    #[arg(long)]
+    pub force: bool,
+}
+
+/**
+A common way of running squiflog.
+
+SYSLOG can only be received over UDP, so there's no scenario for receiving it over TLS.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Scenario {
+    /**
+    Receive SYSLOG over UDP and write CLEF to stdout.
+    */
+    Stdout,
+    /**
+    Run the `datalust/seq-input-syslog` container, receiving SYSLOG over UDP and forwarding events to Seq over HTTP.
+    */
+    Seq,
+    /**
+    Collect the logs of other Docker containers using the syslog logging driver.
+    */
+    Docker,
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /**
+    The file to write the report to. Use `-` to write to stdout.
+    */
+    #[arg(short, long, value_name = "FILE", default_value = "squiflog-doctor.txt")]
+    pub output: PathBuf,
+
+    /**
+    A file of diagnostic events written by the server to stderr,
+    like the output of `docker logs`. Recent errors from it are included.
+    */
+    #[arg(long, value_name = "FILE")]
+    pub diagnostics: Option<PathBuf>,
+
+    /**
+    A file of newline-delimited SYSLOG messages to try parsing.
+    Any that fail are included.
+    */
+    #[arg(long, value_name = "FILE")]
+    pub sample: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /**
+    The capture to replay, in pcap format. Captures in pcapng format
+    can be converted using `editcap -F pcap`.
+    */
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+
+    /**
+    Only replay datagrams sent to this port.
+    */
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    /**
+    Wait between datagrams as long as the capture did, instead of replaying them as fast as possible.
+    */
+    #[arg(long)]
+    pub original_timing: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct TestRulesArgs {
+    /**
+    A file of newline-delimited SYSLOG messages to test. Defaults to stdin.
+    */
+    #[arg(value_name = "FILE", conflicts_with = "messages")]
+    pub path: Option<PathBuf>,
+
+    /**
+    A SYSLOG message to test. Can be given more than once.
+    */
+    #[arg(short, long = "message", value_name = "MESSAGE")]
+    pub messages: Vec<String>,
+
+    /**
+    Read configuration from an env file, like one generated by `squiflog init`,
+    instead of the environment.
+    */
+    #[arg(long, value_name = "FILE")]
+    pub env_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /**
+    The shell to generate completions for.
+    */
+    #[arg(value_name = "SHELL")]
+    pub shell: Shell,
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateClefArgs {
+    /**
+    A file of newline-delimited CLEF to check. Defaults to stdin.
+    */
+    #[arg(value_name = "FILE")]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DashboardArgs {
+    /**
+    The stats address of a server to show, like `127.0.0.1:5140`. Can be given
+    more than once. Defaults to the configured `SYSLOG_STATS_ADDRESS`.
+    */
+    #[arg(short, long = "server", value_name = "ADDRESS")]
+    pub servers: Vec<String>,
+
+    /**
+    How often to redraw the dashboard, in seconds.
+    */
+    #[arg(long, value_name = "SECONDS", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+
+    /**
+    How many hostnames, apps, and drop reasons to show.
+    */
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub top: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct ExtractTemplatesArgs {
+    /**
+    The files of SYSLOG messages to analyze. Defaults to stdin. Glob patterns
+    like `archive/**/*.log.gz` are expanded, and files compressed with gzip
+    are decompressed.
+    */
+    #[arg(value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+
+    /**
+    Split messages using octet counting instead of newlines.
+    */
+    #[arg(long)]
+    pub octet_counted: bool,
+
+    /**
+    How similar a message needs to be to a template to match it, from 0 to 1.
+    Lower values produce fewer, more general templates.
+    */
+    #[arg(long, value_name = "SIMILARITY", default_value_t = 0.4)]
+    pub similarity: f64,
+
+    /**
+    How many leading words of a message are used to narrow down the templates
+    it's compared with.
+    */
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    pub prefix: usize,
+
+    /**
+    Only show the N most common templates.
+    */
+    #[arg(long, value_name = "N")]
+    pub top: Option<usize>,
+
+    /**
+    Only show templates that matched at least this many messages.
+    */
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub min_count: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct UpdateArgs {
+    /**
+    Only check whether a newer release is available, without installing it.
+    */
+    #[arg(long)]
+    pub check_only: bool,
+
+    /**
+    Where to find the latest release, like a mirror of the GitHub releases API
+    for hosts without internet access.
+    */
+    #[arg(long, value_name = "URL")]
+    pub url: Option<String>,
+}
@@ -0,0 +1,543 @@
+/*!
+The `convert` subcommand.
+*/
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        self,
+        BufReader,
+        BufWriter,
+        Read,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+use bytes::{
+    Bytes,
+    BytesMut,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+use libflate::gzip;
+
+use tokio_util::codec::Decoder;
+
+use squiflog::{
+    config::Config,
+    data::{
+        self,
+        output,
+    },
+    error::Error,
+};
+
+use squiflog_core::codec::{
+    NewlineCodec,
+    OctetCountingCodec,
+};
+
+use super::args::ConvertArgs;
+
+// The first bytes of any gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// How much of a file to read at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// How many failures to show for each file
+const MAX_FAILURES: usize = 5;
+
+pub fn run(args: ConvertArgs) -> Result<(), Error> {
+    let config = Config::from_env()?;
+
+    let inputs = expand(&args.patterns)?;
+
+    // Work out where everything goes before converting anything,
+    // so a bad output path doesn't leave a conversion half done
+    let mut outputs = HashSet::new();
+    let mut files = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let output = output_path(&input, args.out_dir.as_deref(), args.gzip)?;
+
+        if output == input {
+            return Err(Error::msg(format!(
+                "`{}` would be converted into itself",
+                input.display()
+            )));
+        }
+
+        if !outputs.insert(output.clone()) {
+            return Err(Error::msg(format!(
+                "more than one file would be converted into `{}`",
+                output.display()
+            )));
+        }
+
+        if !args.force && output.exists() {
+            return Err(Error::msg(format!(
+                "`{}` already exists, use `--force` to replace it",
+                output.display()
+            )));
+        }
+
+        files.push((input, output));
+    }
+
+    if let Some(out_dir) = &args.out_dir {
+        fs::create_dir_all(out_dir)?;
+    }
+
+    let mut total = Stats::default();
+    let mut failed_files = 0;
+    for (input, output) in &files {
+        let file = Convert {
+            config: config.data.clone(),
+            octet_counted: args.octet_counted,
+            gzip: args.gzip,
+        };
+
+        match file.convert(input, output) {
+            Ok(stats) => {
+                eprintln!("{} -> {}: {}", input.display(), output.display(), stats);
+                for failure in &stats.failures {
+                    eprintln!("  {}", failure);
+                }
+
+                total.add(&stats);
+            }
+            Err(err) => {
+                eprintln!("{}: failed to convert: {}", input.display(), err);
+                failed_files += 1;
+            }
+        }
+    }
+
+    eprintln!("converted {} of {} files: {}", files.len() - failed_files, files.len(), total);
+
+    if failed_files > 0 {
+        return Err(Error::msg(format!("{} files couldn't be converted", failed_files)));
+    }
+
+    Ok(())
+}
+
+/**
+Expand glob patterns into the files to convert.
+
+Files are converted in order of the patterns, and sorted by path within each pattern.
+A file that matches more than one pattern is only converted once.
+*/
+pub(super) fn expand(patterns: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        let mut matched = glob::glob(pattern)
+            .map_err(|err| Error::msg(format!("invalid pattern `{}`: {}", pattern, err)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| Error::msg(err.to_string()))?;
+        matched.retain(|path| path.is_file());
+        matched.sort();
+
+        if matched.is_empty() {
+            return Err(Error::msg(format!("no files match `{}`", pattern)));
+        }
+
+        files.extend(matched.into_iter().filter(|path| seen.insert(path.clone())));
+    }
+
+    Ok(files)
+}
+
+/**
+The path of the CLEF file to convert a file into.
+
+Any `.gz` extension is dropped before the `.clef` extension is added,
+so `syslog.1.gz` is converted into `syslog.1.clef`.
+*/
+fn output_path(input: &Path, out_dir: Option<&Path>, gzip: bool) -> Result<PathBuf, Error> {
+    let mut name = input
+        .file_name()
+        .ok_or_else(|| Error::msg(format!("`{}` isn't a file", input.display())))?
+        .to_owned();
+
+    if Path::new(&name).extension().is_some_and(|ext| ext == "gz") {
+        name = Path::new(&name).file_stem().expect("file has a name").to_owned();
+    }
+
+    name.push(if gzip { ".clef.gz" } else { ".clef" });
+
+    let dir = match out_dir {
+        Some(out_dir) => out_dir,
+        None => input.parent().unwrap_or_else(|| Path::new("")),
+    };
+
+    Ok(dir.join(name))
+}
+
+/**
+Converts a single file.
+*/
+struct Convert {
+    config: data::Config,
+    octet_counted: bool,
+    gzip: bool,
+}
+
+impl Convert {
+    fn convert(&self, input: &Path, output: &Path) -> Result<Stats, Error> {
+        let file = File::open(input)?;
+
+        // RFC 3164 messages don't carry a year, so use the one the file was last written in
+        let received = file
+            .metadata()?
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        let reader = decompress(BufReader::new(file))?;
+
+        let sink = Sink::create(output, self.gzip)?;
+        let data = data::Data::builder()
+            .config(self.config.clone())
+            .output(output::Writer::new(sink.clone()))
+            .build();
+
+        let mut stats = Stats::default();
+        let read = |msg| stats.read(&data, msg, received);
+
+        if self.octet_counted {
+            frames(reader, OctetCountingCodec::new(), read)?;
+        } else {
+            frames(reader, NewlineCodec::new(), read)?;
+        }
+
+        sink.finish()?;
+
+        Ok(stats)
+    }
+}
+
+/**
+Read a file, decompressing it if it's gzipped.
+*/
+pub(super) fn decompress(mut reader: BufReader<File>) -> Result<Box<dyn Read>, Error> {
+    use std::io::BufRead;
+
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(gzip::MultiDecoder::new(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/**
+Split the contents of a reader into SYSLOG messages.
+
+Framing errors are passed along without stopping, so the rest of the file can still be converted.
+*/
+pub(super) fn frames<C>(mut reader: impl Read, mut codec: C, mut f: impl FnMut(Result<Bytes, Error>)) -> Result<(), Error>
+where
+    C: Decoder<Item = Result<Bytes, Error>, Error = Error>,
+{
+    let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        if read == 0 {
+            break;
+        }
+
+        buf.extend_from_slice(&chunk[..read]);
+
+        while let Some(frame) = codec.decode(&mut buf)? {
+            f(frame);
+        }
+    }
+
+    while let Some(frame) = codec.decode_eof(&mut buf)? {
+        f(frame);
+    }
+
+    Ok(())
+}
+
+/**
+Statistics for the messages in a converted file.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(super) struct Stats {
+    pub(super) messages: usize,
+    pub(super) rfc5424: usize,
+    pub(super) rfc3164: usize,
+    pub(super) failed: usize,
+    pub(super) failures: Vec<String>,
+}
+
+impl Stats {
+    pub(super) fn read(&mut self, data: &data::Data, msg: Result<Bytes, Error>, received: DateTime<Utc>) {
+        let msg = match msg {
+            // Skip blank lines between messages
+            Ok(msg) if msg.iter().all(u8::is_ascii_whitespace) => return,
+            Ok(msg) => msg,
+            Err(err) => {
+                self.messages += 1;
+                self.fail(err);
+                return;
+            }
+        };
+
+        self.messages += 1;
+
+        match data.read_as_clef_at(&msg, received) {
+            Ok(data::Format::Rfc5424) => self.rfc5424 += 1,
+            Ok(data::Format::Rfc3164) => self.rfc3164 += 1,
+            Err(err) => self.fail(err),
+        }
+    }
+
+    fn fail(&mut self, err: Error) {
+        self.failed += 1;
+
+        if self.failures.len() < MAX_FAILURES {
+            self.failures.push(format!("message {}: {}", self.messages, err));
+        }
+    }
+
+    fn add(&mut self, other: &Stats) {
+        self.messages += other.messages;
+        self.rfc5424 += other.rfc5424;
+        self.rfc3164 += other.rfc3164;
+        self.failed += other.failed;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} messages ({} RFC 5424, {} RFC 3164), {} failed",
+            self.messages, self.rfc5424, self.rfc3164, self.failed
+        )
+    }
+}
+
+/**
+A CLEF file being written.
+
+The file is shared with the output, so it can be finished once all events have been written.
+*/
+#[derive(Clone)]
+struct Sink(Arc<Mutex<Option<Target>>>);
+
+enum Target {
+    Plain(BufWriter<File>),
+    Gzip(gzip::Encoder<BufWriter<File>>),
+}
+
+impl Sink {
+    fn create(path: &Path, gzip: bool) -> Result<Self, Error> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let target = if gzip {
+            Target::Gzip(gzip::Encoder::new(file)?)
+        } else {
+            Target::Plain(file)
+        };
+
+        Ok(Sink(Arc::new(Mutex::new(Some(target)))))
+    }
+
+    fn finish(&self) -> Result<(), Error> {
+        let target = self.lock()?.take();
+
+        let mut file = match target {
+            Some(Target::Plain(file)) => file,
+            Some(Target::Gzip(encoder)) => encoder.finish().into_result()?,
+            None => return Ok(()),
+        };
+
+        file.flush()?;
+        file.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+
+        Ok(())
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, Option<Target>>> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("failed to lock the CLEF file"))
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.lock()? {
+            Some(Target::Plain(file)) => file.write(buf),
+            Some(Target::Gzip(encoder)) => encoder.write(buf),
+            None => Err(io::Error::other("the CLEF file has already been finished")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.lock()? {
+            Some(Target::Plain(file)) => file.flush(),
+            Some(Target::Gzip(encoder)) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::{
+        env,
+        process,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("squiflog-convert-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create temp dir");
+
+        dir
+    }
+
+    fn read_clef(path: &Path) -> Vec<Value> {
+        let mut clef = String::new();
+        decompress(BufReader::new(File::open(path).expect("failed to open CLEF")))
+            .expect("failed to read CLEF")
+            .read_to_string(&mut clef)
+            .expect("failed to read CLEF");
+
+        clef.lines()
+            .map(|line| serde_json::from_str(line).expect("invalid CLEF"))
+            .collect()
+    }
+
+    #[test]
+    fn output_path_replaces_extensions() {
+        let cases = [
+            ("logs/syslog", None, false, "logs/syslog.clef"),
+            ("logs/syslog.1.gz", None, false, "logs/syslog.1.clef"),
+            ("logs/syslog.1.gz", None, true, "logs/syslog.1.clef.gz"),
+            ("logs/syslog.log", Some("out"), false, "out/syslog.log.clef"),
+        ];
+
+        for (input, out_dir, gzip, expected) in cases {
+            let output = output_path(Path::new(input), out_dir.map(Path::new), gzip).expect("invalid path");
+
+            assert_eq!(Path::new(expected), output);
+        }
+    }
+
+    #[test]
+    fn stats_count_formats_and_failures() {
+        let data = data::Data::builder()
+            .strictness(data::Strictness::Rfc5424)
+            .output(output::Writer::new(io::sink()))
+            .build();
+
+        let mut stats = Stats::default();
+        frames(
+            &b"<30>1 - host app - - - hello\n\n<34>Oct 11 22:14:15 mymachine su: failed\n"[..],
+            NewlineCodec::new(),
+            |msg| stats.read(&data, msg, Utc::now()),
+        )
+        .expect("failed to read frames");
+
+        assert_eq!(2, stats.messages);
+        assert_eq!(1, stats.rfc5424);
+        assert_eq!(1, stats.failed);
+        assert!(stats.failures[0].starts_with("message 2: invalid header"), "{:?}", stats.failures);
+    }
+
+    #[test]
+    fn convert_gzipped_file() {
+        let dir = temp_dir("gzip");
+        let input = dir.join("syslog.1.gz");
+        let output = dir.join("syslog.1.clef.gz");
+
+        let mut encoder = gzip::Encoder::new(File::create(&input).expect("failed to create input")).unwrap();
+        encoder
+            .write_all(b"<30>1 2020-02-13T00:51:39Z host app - - - first\n<34>Oct 11 22:14:15 mymachine su: second\n")
+            .unwrap();
+        encoder.finish().into_result().unwrap();
+
+        let convert = Convert {
+            config: data::Config::default(),
+            octet_counted: false,
+            gzip: true,
+        };
+        let stats = convert.convert(&input, &output).expect("failed to convert");
+
+        assert_eq!(1, stats.rfc5424);
+        assert_eq!(1, stats.rfc3164);
+        assert_eq!(0, stats.failed);
+
+        let events = read_clef(&output);
+        let messages = events.iter().map(|evt| evt["@m"].as_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(vec!["first", "su: second"], messages);
+
+        fs::remove_dir_all(&dir).expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn convert_continues_after_invalid_frames() {
+        let dir = temp_dir("framing");
+        let input = dir.join("syslog.log");
+        let output = dir.join("syslog.log.clef");
+
+        let first = "<30>1 2020-02-13T00:51:39Z host app - - - first";
+        let last = "<30>1 2020-02-13T00:51:40Z host app - - - last";
+        fs::write(
+            &input,
+            format!("{} {}12<30>1 broken {} {}", first.len(), first, last.len(), last),
+        )
+        .expect("failed to write input");
+
+        let convert = Convert {
+            config: data::Config::default(),
+            octet_counted: true,
+            gzip: false,
+        };
+        let stats = convert.convert(&input, &output).expect("failed to convert");
+
+        assert_eq!(3, stats.messages);
+        assert_eq!(2, stats.rfc5424);
+        assert_eq!(1, stats.failed);
+        assert!(stats.failures[0].starts_with("message 2: invalid framing"), "{:?}", stats.failures);
+
+        let events = read_clef(&output);
+        let messages = events.iter().map(|evt| evt["@m"].as_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(vec!["first", "last"], messages);
+
+        fs::remove_dir_all(&dir).expect("failed to remove temp dir");
+    }
+}
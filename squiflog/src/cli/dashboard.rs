@@ -0,0 +1,406 @@
+/*!
+The `dashboard` subcommand.
+
+The dashboard doesn't receive any messages itself. It polls the stats that
+running servers serve on their `SYSLOG_STATS_ADDRESS`.
+*/
+
+use std::{
+    collections::{
+        BTreeMap,
+        VecDeque,
+    },
+    fmt::Write as _,
+    io::{
+        self,
+        IsTerminal,
+        Write,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    runtime::Runtime,
+    signal::ctrl_c,
+    time::timeout,
+};
+
+use squiflog::{
+    config::Config,
+    error::Error,
+    stats::Snapshot,
+};
+
+use super::args::DashboardArgs;
+
+// How many intervals of throughput to average over
+const HISTORY: usize = 60;
+
+// Stats are small, so a server that's slow to serve them is struggling
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const CLEAR: &str = "\x1b[H\x1b[J";
+
+pub fn run(args: DashboardArgs) -> Result<(), Error> {
+    let servers = if args.servers.is_empty() {
+        let addr = Config::from_env()?.stats.addr.ok_or_else(|| {
+            Error::msg("no server to show, pass `--server` with the address a server's `SYSLOG_STATS_ADDRESS` is set to")
+        })?;
+
+        vec![addr]
+    } else {
+        args.servers
+    };
+
+    let dashboard = Dashboard {
+        interval: Duration::from_secs(args.interval),
+        top: args.top,
+        terminal: io::stdout().is_terminal(),
+    };
+
+    dashboard.draw_until_interrupted(servers.into_iter().map(Listener::new).collect())
+}
+
+struct Dashboard {
+    interval: Duration,
+    top: usize,
+    terminal: bool,
+}
+
+impl Dashboard {
+    /**
+    Poll servers and redraw the dashboard every interval until interrupted.
+
+    The last frame is left on the terminal.
+    */
+    fn draw_until_interrupted(&self, mut listeners: Vec<Listener>) -> Result<(), Error> {
+        let runtime = Runtime::new()?;
+        let mut interrupted = Box::pin(ctrl_c());
+
+        let agent = ureq::AgentBuilder::new().timeout(POLL_TIMEOUT).build();
+        let mut stdout = io::stdout();
+
+        if self.terminal {
+            write!(stdout, "{}", ENTER_SCREEN)?;
+        }
+
+        let frame = loop {
+            for listener in &mut listeners {
+                listener.update(poll(&agent, &listener.server));
+            }
+
+            let frame = render(&listeners, self.interval, self.top);
+
+            if self.terminal {
+                write!(stdout, "{}{}", CLEAR, frame)?;
+            } else {
+                writeln!(stdout, "{}", frame)?;
+            }
+            stdout.flush()?;
+
+            if runtime.block_on(async { timeout(self.interval, &mut interrupted).await }).is_ok() {
+                break frame;
+            }
+        };
+
+        if self.terminal {
+            write!(stdout, "{}", LEAVE_SCREEN)?;
+            write!(stdout, "{}", frame)?;
+        }
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+fn poll(agent: &ureq::Agent, server: &str) -> Result<Snapshot, Error> {
+    let url = if server.contains("://") {
+        format!("{}/stats", server.trim_end_matches('/'))
+    } else {
+        format!("http://{}/stats", server)
+    };
+
+    let body = agent
+        .get(&url)
+        .call()
+        .map_err(|err| Error::msg(err.to_string()))?
+        .into_string()?;
+
+    serde_json::from_str(&body).map_err(|err| Error::msg(format!("invalid stats from {}: {}", url, err)))
+}
+
+/**
+The stats polled from a server, and the throughput of its listener.
+*/
+struct Listener {
+    server: String,
+    latest: Option<Snapshot>,
+    error: Option<String>,
+    history: VecDeque<u64>,
+}
+
+impl Listener {
+    fn new(server: String) -> Self {
+        Listener {
+            server,
+            latest: None,
+            error: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn update(&mut self, polled: Result<Snapshot, Error>) {
+        let snapshot = match polled {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return;
+            }
+        };
+
+        if let Some(latest) = &self.latest {
+            // A server that's restarted since it was last polled counts from zero again
+            let received = if snapshot.received >= latest.received && snapshot.uptime_secs >= latest.uptime_secs {
+                snapshot.received - latest.received
+            } else {
+                snapshot.received
+            };
+
+            if self.history.len() == HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(received);
+        }
+
+        self.latest = Some(snapshot);
+        self.error = None;
+    }
+
+    fn render(&self, frame: &mut String, interval: Duration) {
+        let latest = match (&self.latest, &self.error) {
+            (Some(latest), None) => latest,
+            (_, error) => {
+                let _ = writeln!(frame, "{}", self.server);
+                let _ = writeln!(frame, "  unreachable: {}", error.as_deref().unwrap_or("no stats yet"));
+
+                return;
+            }
+        };
+
+        let secs = latest.uptime_secs;
+        let _ = writeln!(
+            frame,
+            "{} via {} (up {:02}:{:02}:{:02})",
+            latest.listener,
+            self.server,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+
+        let rate = |count: u64, intervals: usize| count as f64 / (intervals as f64 * interval.as_secs_f64()).max(1.0);
+        let now = self.history.back().copied().unwrap_or_default();
+        let average = rate(self.history.iter().sum(), self.history.len());
+        let dropped = if latest.received > 0 {
+            latest.dropped as f64 * 100.0 / latest.received as f64
+        } else {
+            0.0
+        };
+
+        let _ = writeln!(frame, "  {:>10.1} msg/s now", rate(now, 1));
+        let _ = writeln!(
+            frame,
+            "  {:>10.1} msg/s over the last {}s",
+            average,
+            self.history.len() as u64 * interval.as_secs()
+        );
+        let _ = writeln!(frame, "  {:>10} received", latest.received);
+        let _ = writeln!(frame, "  {:>10} processed", latest.processed);
+        let _ = writeln!(frame, "  {:>10} dropped ({:.1}%)", latest.dropped, dropped);
+    }
+}
+
+fn render(listeners: &[Listener], interval: Duration, top: usize) -> String {
+    let mut frame = String::new();
+
+    let _ = writeln!(frame, "squiflog dashboard\n");
+
+    for listener in listeners {
+        listener.render(&mut frame, interval);
+        let _ = writeln!(frame);
+    }
+
+    // Names are counted across all the servers that could be polled
+    let mut hostnames = BTreeMap::new();
+    let mut apps = BTreeMap::new();
+    let mut formats = BTreeMap::new();
+    let mut drop_reasons = BTreeMap::new();
+
+    for latest in listeners
+        .iter()
+        .filter(|listener| listener.error.is_none())
+        .filter_map(|listener| listener.latest.as_ref())
+    {
+        for (counts, merged) in [
+            (&latest.hostnames, &mut hostnames),
+            (&latest.apps, &mut apps),
+            (&latest.formats, &mut formats),
+            (&latest.drop_reasons, &mut drop_reasons),
+        ] {
+            for (name, count) in counts {
+                *merged.entry(&**name).or_default() += count;
+            }
+        }
+    }
+
+    for (heading, counts) in [
+        ("Top hostnames", &hostnames),
+        ("Top apps", &apps),
+        ("Formats", &formats),
+        ("Drop reasons", &drop_reasons),
+    ] {
+        let _ = writeln!(frame, "{}", heading);
+
+        if counts.is_empty() {
+            let _ = writeln!(frame, "  {:>10}", "none");
+        }
+
+        for (name, count) in top_n(counts, top) {
+            let _ = writeln!(frame, "  {:>10} {}", count, name);
+        }
+
+        let _ = writeln!(frame);
+    }
+
+    frame.pop();
+    frame
+}
+
+fn top_n<'a>(counts: &BTreeMap<&'a str, u64>, n: usize) -> Vec<(&'a str, u64)> {
+    let mut counts = counts.iter().map(|(name, count)| (*name, *count)).collect::<Vec<_>>();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+    counts.truncate(n);
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(listener: &str, uptime_secs: u64, received: u64) -> Snapshot {
+        Snapshot {
+            listener: listener.to_owned(),
+            uptime_secs,
+            received,
+            ..Snapshot::default()
+        }
+    }
+
+    fn counts(counts: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        counts.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+    }
+
+    #[test]
+    fn listeners_are_rendered() {
+        let mut web = Listener::new("10.0.0.1:5140".to_owned());
+        web.update(Ok(snapshot("udp://0.0.0.0:514", 3720, 0)));
+        web.update(Ok(Snapshot {
+            processed: 4,
+            dropped: 1,
+            formats: counts(&[("RFC 5424", 3), ("RFC 3164", 1)]),
+            hostnames: counts(&[("web-1", 2), ("web-2", 1), ("-", 1)]),
+            apps: counts(&[("nginx", 2), ("sshd", 1), ("-", 1)]),
+            drop_reasons: counts(&[("invalid header: missing start delimiter", 1)]),
+            ..snapshot("udp://0.0.0.0:514", 3725, 5)
+        }));
+
+        let mut db = Listener::new("10.0.0.2:5140".to_owned());
+        db.update(Ok(Snapshot {
+            hostnames: counts(&[("web-1", 2), ("db-1", 3)]),
+            ..snapshot("udp://0.0.0.0:5514", 10, 5)
+        }));
+        db.update(Err(Error::msg("connection refused")));
+
+        let frame = render(&[web, db], Duration::from_secs(5), 2);
+
+        assert_eq!(
+            "squiflog dashboard
+
+udp://0.0.0.0:514 via 10.0.0.1:5140 (up 01:02:05)
+         1.0 msg/s now
+         1.0 msg/s over the last 5s
+           5 received
+           4 processed
+           1 dropped (20.0%)
+
+10.0.0.2:5140
+  unreachable: connection refused
+
+Top hostnames
+           2 web-1
+           1 -
+
+Top apps
+           2 nginx
+           1 -
+
+Formats
+           3 RFC 5424
+           1 RFC 3164
+
+Drop reasons
+           1 invalid header: missing start delimiter
+",
+            frame
+        );
+    }
+
+    #[test]
+    fn counts_are_merged_across_listeners() {
+        let mut web = Listener::new("10.0.0.1:5140".to_owned());
+        web.update(Ok(Snapshot {
+            hostnames: counts(&[("web-1", 2), ("web-2", 1)]),
+            ..snapshot("udp://0.0.0.0:514", 1, 3)
+        }));
+
+        let mut db = Listener::new("10.0.0.2:5140".to_owned());
+        db.update(Ok(Snapshot {
+            hostnames: counts(&[("web-2", 2), ("db-1", 1)]),
+            ..snapshot("udp://0.0.0.0:5514", 1, 3)
+        }));
+
+        let frame = render(&[web, db], Duration::from_secs(1), 5);
+        assert!(
+            frame.contains("Top hostnames\n           3 web-2\n           2 web-1\n           1 db-1\n"),
+            "{}",
+            frame
+        );
+        assert!(frame.contains("Drop reasons\n        none\n"), "{}", frame);
+    }
+
+    #[test]
+    fn throughput_is_averaged_over_history() {
+        let mut listener = Listener::new("127.0.0.1:5140".to_owned());
+
+        let mut received = 0;
+        for (uptime, count) in (0..(HISTORY as u64 + 11)).enumerate() {
+            received += count;
+            listener.update(Ok(snapshot("udp://0.0.0.0:514", uptime as u64, received)));
+        }
+
+        assert_eq!(HISTORY, listener.history.len());
+        assert_eq!(Some(&11), listener.history.front());
+
+        let mut frame = String::new();
+        listener.render(&mut frame, Duration::from_secs(2));
+        assert!(frame.contains("        35.0 msg/s now\n"), "{}", frame);
+        assert!(frame.contains("        20.2 msg/s over the last 120s\n"), "{}", frame);
+
+        // A restarted server counts from zero again
+        listener.update(Ok(snapshot("udp://0.0.0.0:514", 2, 8)));
+        assert_eq!(Some(&8), listener.history.back());
+    }
+}
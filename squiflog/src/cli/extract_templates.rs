@@ -0,0 +1,378 @@
+/*!
+The `extract-templates` subcommand.
+
+Templates are extracted using the Drain algorithm described in
+"Drain: An Online Log Parsing Approach with Fixed Depth Tree" (He et al, 2017).
+Messages are grouped by their app and number of words, then by their first few
+words, and finally compared with each template in the group, word by word.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::File,
+    io::{
+        self,
+        BufReader,
+        Read,
+    },
+};
+
+use bytes::Bytes;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+use squiflog::{
+    config::Config,
+    data,
+    error::Error,
+};
+
+use squiflog_core::codec::{
+    NewlineCodec,
+    OctetCountingCodec,
+};
+
+use super::{
+    args::ExtractTemplatesArgs,
+    convert,
+};
+
+// Stands in for the words of a template that vary between messages
+const WILDCARD: &str = "<*>";
+
+// How many distinct words a node can branch on before the rest share a wildcard
+const MAX_CHILDREN: usize = 100;
+
+pub fn run(args: ExtractTemplatesArgs) -> Result<(), Error> {
+    if !(0.0..=1.0).contains(&args.similarity) {
+        return Err(Error::msg("the similarity must be between 0 and 1"));
+    }
+
+    let config = Config::from_env()?;
+
+    let mut corpus = Corpus {
+        config: config.data,
+        drain: Drain::new(args.similarity, args.prefix),
+        received: Utc::now(),
+        messages: 0,
+        failed: 0,
+    };
+
+    if args.patterns.is_empty() {
+        corpus.read(io::stdin().lock(), args.octet_counted)?;
+    } else {
+        for path in convert::expand(&args.patterns)? {
+            let reader = convert::decompress(BufReader::new(File::open(&path)?))?;
+            corpus.read(reader, args.octet_counted)?;
+        }
+    }
+
+    print!("{}", corpus.drain.report(corpus.messages, args.top, args.min_count));
+    eprintln!(
+        "found {} templates in {} messages, {} failed",
+        corpus.drain.clusters.len(),
+        corpus.messages,
+        corpus.failed
+    );
+
+    Ok(())
+}
+
+/**
+The messages being analyzed.
+*/
+struct Corpus {
+    config: data::Config,
+    drain: Drain,
+    // Templates don't depend on timestamps, so any year will do for RFC 3164
+    received: DateTime<Utc>,
+    messages: usize,
+    failed: usize,
+}
+
+impl Corpus {
+    fn read(&mut self, reader: impl Read, octet_counted: bool) -> Result<(), Error> {
+        let add = |msg| self.add(msg);
+
+        if octet_counted {
+            convert::frames(reader, OctetCountingCodec::new(), add)
+        } else {
+            convert::frames(reader, NewlineCodec::new(), add)
+        }
+    }
+
+    fn add(&mut self, msg: Result<Bytes, Error>) {
+        let msg = match msg {
+            // Skip blank lines between messages
+            Ok(msg) if msg.iter().all(u8::is_ascii_whitespace) => return,
+            Ok(msg) => msg,
+            Err(_) => {
+                self.failed += 1;
+                return;
+            }
+        };
+
+        match self.config.parse(&msg, &self.received) {
+            Ok((syslog, _)) => {
+                self.messages += 1;
+                self.drain.add(
+                    syslog.app_name.as_deref().unwrap_or("-"),
+                    syslog.message.as_deref().unwrap_or_default(),
+                );
+            }
+            Err(_) => self.failed += 1,
+        }
+    }
+}
+
+/**
+A Drain parse tree.
+*/
+struct Drain {
+    similarity: f64,
+    prefix: usize,
+    groups: HashMap<(String, usize), Node>,
+    clusters: Vec<Cluster>,
+}
+
+/**
+A node in the parse tree, branching on a word of the message.
+
+Only the nodes at the end of a prefix have clusters.
+*/
+#[derive(Default)]
+struct Node {
+    children: HashMap<String, Node>,
+    clusters: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Cluster {
+    app: String,
+    template: Vec<String>,
+    count: usize,
+}
+
+impl Drain {
+    fn new(similarity: f64, prefix: usize) -> Self {
+        Drain {
+            similarity,
+            prefix,
+            groups: HashMap::new(),
+            clusters: Vec::new(),
+        }
+    }
+
+    /**
+    Add a message to the cluster with the most similar template, or start a new one.
+    */
+    fn add(&mut self, app: &str, text: &str) -> usize {
+        let words = text.split_whitespace().collect::<Vec<_>>();
+
+        match self.find(app, &words) {
+            Some(id) => {
+                let cluster = &mut self.clusters[id];
+
+                for (template, word) in cluster.template.iter_mut().zip(&words) {
+                    if template != word {
+                        *template = WILDCARD.to_owned();
+                    }
+                }
+                cluster.count += 1;
+
+                id
+            }
+            None => self.insert(app, &words),
+        }
+    }
+
+    fn find(&self, app: &str, words: &[&str]) -> Option<usize> {
+        let mut node = self.groups.get(&(app.to_owned(), words.len()))?;
+        for word in words.iter().take(self.prefix) {
+            node = node.children.get(*word).or_else(|| node.children.get(WILDCARD))?;
+        }
+
+        // Prefer the most similar template, then the most general one
+        let (similarity, _, id) = node
+            .clusters
+            .iter()
+            .map(|&id| {
+                let (similarity, wildcards) = self.clusters[id].similarity(words);
+                (similarity, wildcards, id)
+            })
+            .max_by(|(a, a_wildcards, _), (b, b_wildcards, _)| a.total_cmp(b).then(a_wildcards.cmp(b_wildcards)))?;
+
+        if similarity >= self.similarity {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    fn insert(&mut self, app: &str, words: &[&str]) -> usize {
+        let id = self.clusters.len();
+
+        let mut node = self.groups.entry((app.to_owned(), words.len())).or_default();
+        for word in words.iter().take(self.prefix) {
+            // Words with digits are likely to vary, so they share a wildcard instead of branching
+            let key = if !node.children.contains_key(*word)
+                && (word.chars().any(|c| c.is_ascii_digit()) || node.children.len() >= MAX_CHILDREN)
+            {
+                WILDCARD
+            } else {
+                word
+            };
+
+            node = node.children.entry(key.to_owned()).or_default();
+        }
+        node.clusters.push(id);
+
+        self.clusters.push(Cluster {
+            app: app.to_owned(),
+            template: words.iter().map(|word| (*word).to_owned()).collect(),
+            count: 1,
+        });
+
+        id
+    }
+
+    /**
+    The templates as a table, most common first.
+    */
+    fn report(&self, messages: usize, top: Option<usize>, min_count: usize) -> String {
+        let mut clusters = self
+            .clusters
+            .iter()
+            .filter(|cluster| cluster.count >= min_count)
+            .collect::<Vec<_>>();
+        clusters.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.app.cmp(&b.app))
+                .then_with(|| a.template.cmp(&b.template))
+        });
+        clusters.truncate(top.unwrap_or(usize::MAX));
+
+        let app_width = clusters.iter().map(|cluster| cluster.app.len()).max().unwrap_or_default().max(3);
+
+        let mut report = String::new();
+        let _ = writeln!(report, "{:>8} {:>6}  {:<app_width$}  TEMPLATE", "COUNT", "%", "APP");
+        for cluster in clusters {
+            let _ = writeln!(
+                report,
+                "{:>8} {:>5.1}%  {:<app_width$}  {}",
+                cluster.count,
+                cluster.count as f64 * 100.0 / messages.max(1) as f64,
+                cluster.app,
+                cluster.template.join(" ")
+            );
+        }
+
+        report
+    }
+}
+
+impl Cluster {
+    /**
+    The fraction of words in a message that match the template, and the number of wildcards in it.
+
+    Wildcards don't count as matches, so templates don't become too general.
+    */
+    fn similarity(&self, words: &[&str]) -> (f64, usize) {
+        if words.is_empty() {
+            return (1.0, 0);
+        }
+
+        let mut matched = 0;
+        let mut wildcards = 0;
+        for (template, word) in self.template.iter().zip(words) {
+            if template == WILDCARD {
+                wildcards += 1;
+            } else if template == word {
+                matched += 1;
+            }
+        }
+
+        (matched as f64 / words.len() as f64, wildcards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates(messages: &[(&str, &str)]) -> Vec<(String, String, usize)> {
+        let mut drain = Drain::new(0.4, 2);
+        for (app, text) in messages {
+            drain.add(app, text);
+        }
+
+        drain
+            .clusters
+            .into_iter()
+            .map(|cluster| (cluster.app, cluster.template.join(" "), cluster.count))
+            .collect()
+    }
+
+    #[test]
+    fn similar_messages_are_merged() {
+        let templates = templates(&[
+            ("sshd", "Failed password for root from 10.0.0.1 port 22 ssh2"),
+            ("sshd", "Failed password for admin from 10.0.0.2 port 2222 ssh2"),
+            ("sshd", "Failed password for admin from 10.0.0.9 port 2222 ssh2"),
+            ("sshd", "Accepted password for root from 10.0.0.1 port 22 ssh2"),
+            ("cron", "12 jobs scheduled"),
+            ("cron", "15 jobs scheduled"),
+        ]);
+
+        assert_eq!(
+            vec![
+                (
+                    "sshd".to_owned(),
+                    "Failed password for <*> from <*> port <*> ssh2".to_owned(),
+                    3
+                ),
+                ("sshd".to_owned(), "Accepted password for root from 10.0.0.1 port 22 ssh2".to_owned(), 1),
+                ("cron".to_owned(), "<*> jobs scheduled".to_owned(), 2),
+            ],
+            templates
+        );
+    }
+
+    #[test]
+    fn dissimilar_messages_are_kept_apart() {
+        let templates = templates(&[
+            // Different apps
+            ("nginx", "upstream timed out"),
+            ("haproxy", "upstream timed out"),
+            // Different numbers of words
+            ("nginx", "upstream timed out while connecting"),
+            // Different words after the prefix
+            ("kernel", "usb 1-1: new high-speed USB device number 2"),
+            ("kernel", "usb 1-1: USB disconnect, device number 2 gone"),
+        ]);
+
+        assert_eq!(5, templates.len(), "{:?}", templates);
+    }
+
+    #[test]
+    fn report_is_sorted_and_filtered() {
+        let mut drain = Drain::new(0.4, 2);
+        for text in ["disk full", "disk full", "disk full", "link down", "link down", "fan failed"] {
+            drain.add("kernel", text);
+        }
+
+        assert_eq!(
+            "   COUNT      %  APP     TEMPLATE\n       3  50.0%  kernel  disk full\n       2  33.3%  kernel  link down\n",
+            drain.report(6, None, 2)
+        );
+        assert_eq!(
+            "   COUNT      %  APP     TEMPLATE\n       3  50.0%  kernel  disk full\n",
+            drain.report(6, Some(1), 1)
+        );
+    }
+}
@@ -0,0 +1,425 @@
+/*!
+The `init` subcommand.
+*/
+
+use std::{
+    fmt::Write as _,
+    fs::OpenOptions,
+    io::{
+        self,
+        BufRead,
+        IsTerminal,
+        Write,
+    },
+    path::Path,
+};
+
+use clap::ValueEnum;
+
+use squiflog::error::Error;
+
+use super::args::{
+    InitArgs,
+    Scenario,
+};
+
+const DEFAULT_LISTEN: &str = "udp://0.0.0.0:514";
+const DEFAULT_SEQ_ADDRESS: &str = "http://localhost:5341";
+
+pub fn run(args: InitArgs) -> Result<(), Error> {
+    let settings = match args.scenario {
+        Some(scenario) => Settings {
+            scenario,
+            listen: args.listen.clone().unwrap_or_else(|| DEFAULT_LISTEN.to_owned()),
+            seq_address: args.seq_address.clone().unwrap_or_else(|| DEFAULT_SEQ_ADDRESS.to_owned()),
+            api_key: args.api_key.clone(),
+            diagnostics: args.diagnostics,
+        },
+        None if io::stdin().is_terminal() => prompt(&args, &mut io::stdin().lock(), &mut io::stderr())?,
+        None => {
+            return Err(Error::msg(
+                "stdin isn't interactive, so the scenario needs to be given using `--scenario`",
+            ))
+        }
+    };
+
+    settings.validate()?;
+
+    if args.output == Path::new("-") {
+        io::stdout().write_all(settings.render("squiflog.env").as_bytes())?;
+        return Ok(());
+    }
+
+    let file_name = args.output.file_name().unwrap_or_default().to_string_lossy();
+    let rendered = settings.render(&file_name);
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!args.force)
+        .open(&args.output)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => Error::msg(format!(
+                "`{}` already exists, use `--force` to replace it",
+                args.output.display()
+            )),
+            _ => err.into(),
+        })?;
+    file.write_all(rendered.as_bytes())?;
+
+    eprintln!("wrote the {} configuration to `{}`", settings.scenario.name(), args.output.display());
+
+    Ok(())
+}
+
+/**
+Ask for any settings that weren't given as arguments.
+*/
+fn prompt(args: &InitArgs, input: &mut impl BufRead, output: &mut impl Write) -> Result<Settings, Error> {
+    let scenarios = Scenario::value_variants();
+
+    writeln!(output, "Where will squiflog run?")?;
+    for (i, scenario) in scenarios.iter().enumerate() {
+        writeln!(output, "  {}) {}: {}", i + 1, scenario.name(), scenario.description())?;
+    }
+    writeln!(
+        output,
+        "SYSLOG over TLS (usually on port 6514) can't be received yet, only UDP. Use a relay like rsyslog to forward it over UDP."
+    )?;
+
+    let scenario = loop {
+        let answer = ask(input, output, "Scenario", Some("1"))?;
+
+        let chosen = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| scenarios.get(i.wrapping_sub(1)).copied())
+            .or_else(|| Scenario::from_str(&answer, true).ok());
+
+        match chosen {
+            Some(scenario) => break scenario,
+            None => writeln!(output, "`{}` isn't one of the scenarios", answer)?,
+        }
+    };
+
+    let listen = match &args.listen {
+        Some(listen) => listen.clone(),
+        None => ask(input, output, "SYSLOG address", Some(DEFAULT_LISTEN))?,
+    };
+
+    let (seq_address, api_key) = if scenario.forwards_to_seq() {
+        let seq_address = match &args.seq_address {
+            Some(seq_address) => seq_address.clone(),
+            None => ask(input, output, "Seq address", Some(DEFAULT_SEQ_ADDRESS))?,
+        };
+
+        let api_key = match &args.api_key {
+            Some(api_key) => Some(api_key.clone()),
+            None => Some(ask(input, output, "Seq API key (optional)", None)?).filter(|key| !key.is_empty()),
+        };
+
+        (seq_address, api_key)
+    } else {
+        (
+            args.seq_address.clone().unwrap_or_else(|| DEFAULT_SEQ_ADDRESS.to_owned()),
+            args.api_key.clone(),
+        )
+    };
+
+    let diagnostics = args.diagnostics || {
+        let answer = ask(input, output, "Enable diagnostics? [y/N]", Some("n"))?;
+        answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+    };
+
+    Ok(Settings {
+        scenario,
+        listen,
+        seq_address,
+        api_key,
+        diagnostics,
+    })
+}
+
+fn ask(input: &mut impl BufRead, output: &mut impl Write, question: &str, default: Option<&str>) -> Result<String, Error> {
+    match default {
+        Some(default) => write!(output, "{} [{}]: ", question, default)?,
+        None => write!(output, "{}: ", question)?,
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(Error::msg("stdin was closed before all settings were given"));
+    }
+
+    match answer.trim() {
+        "" => Ok(default.unwrap_or_default().to_owned()),
+        answer => Ok(answer.to_owned()),
+    }
+}
+
+impl Scenario {
+    fn name(self) -> &'static str {
+        match self {
+            Scenario::Stdout => "stdout",
+            Scenario::Seq => "seq",
+            Scenario::Docker => "docker",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Scenario::Stdout => "receive SYSLOG over UDP and write CLEF to stdout",
+            Scenario::Seq => "receive SYSLOG over UDP in the `datalust/seq-input-syslog` container, forwarding events to Seq",
+            Scenario::Docker => "collect the logs of other Docker containers using the syslog logging driver",
+        }
+    }
+
+    fn forwards_to_seq(self) -> bool {
+        matches!(self, Scenario::Seq | Scenario::Docker)
+    }
+}
+
+/**
+The settings to write into a configuration file.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Settings {
+    scenario: Scenario,
+    listen: String,
+    seq_address: String,
+    api_key: Option<String>,
+    diagnostics: bool,
+}
+
+impl Settings {
+    fn validate(&self) -> Result<(), Error> {
+        let values = [&*self.listen, &*self.seq_address, self.api_key.as_deref().unwrap_or_default()];
+        if values.iter().any(|value| value.contains(['\r', '\n'])) {
+            return Err(Error::msg("settings can't contain newlines"));
+        }
+
+        if self.listen.starts_with("tls://") {
+            return Err(Error::msg(format!(
+                "can't listen on `{}`, because TLS input isn't supported yet, only UDP addresses like `{}` are; use a relay like rsyslog to forward TLS over UDP",
+                self.listen, DEFAULT_LISTEN
+            )));
+        }
+
+        if !self.listen.starts_with("udp://") && self.listen.contains("://") {
+            return Err(Error::msg(format!(
+                "can't listen on `{}`, only UDP addresses like `{}` are supported",
+                self.listen, DEFAULT_LISTEN
+            )));
+        }
+
+        Ok(())
+    }
+
+    /**
+    Render the settings as an env file.
+    */
+    fn render(&self, file_name: &str) -> String {
+        let mut env = String::new();
+        let seq = self.scenario.forwards_to_seq();
+
+        let _ = writeln!(
+            env,
+            "# squiflog configuration for the `{}` scenario, generated by `squiflog init`.",
+            self.scenario.name()
+        );
+        let _ = writeln!(env, "#");
+        let _ = writeln!(env, "# squiflog reads its configuration from environment variables. Pass this file");
+        let _ = writeln!(
+            env,
+            "# to `docker run --env-file {}`, or load it into a shell with `set -a; . ./{}; set +a`.",
+            file_name, file_name
+        );
+        let _ = writeln!(env, "# Commented-out settings aren't needed for this scenario.");
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# The address to receive SYSLOG messages on. Only UDP is supported,");
+        let _ = writeln!(env, "# so SYSLOG over TCP or TLS (usually port 6514) can't be received directly yet.");
+        let _ = writeln!(env, "# Terminate TLS in a relay like rsyslog or syslog-ng and forward it here over UDP.");
+        setting(&mut env, true, "SYSLOG_ADDRESS", &self.listen);
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# Whether to write diagnostic logs and metrics to stderr (`True` or `False`).");
+        setting(&mut env, self.diagnostics, "SYSLOG_ENABLE_DIAGNOSTICS", "True");
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# The address to serve stats on for `squiflog dashboard`. Stats aren't collected without it.");
+        setting(&mut env, false, "SYSLOG_STATS_ADDRESS", "127.0.0.1:5140");
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# Which SYSLOG formats to accept. `Lenient` falls back to RFC 3164 for messages");
+        let _ = writeln!(env, "# that aren't RFC 5424, and `Rfc5424` rejects them.");
+        setting(&mut env, false, "SYSLOG_STRICTNESS", "Lenient");
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# Whether fields from the SYSLOG header (`Header`) or structured data");
+        let _ = writeln!(env, "# (`StructuredData`) are kept when they have the same name.");
+        setting(&mut env, false, "SYSLOG_PRECEDENCE", "StructuredData");
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# Properties to attach to every event, then properties to rename and remove.");
+        let _ = writeln!(env, "# Check their effect with `squiflog test-rules --env-file {}`.", file_name);
+        setting(&mut env, false, "SYSLOG_PROPERTIES", "environment=production,region=us");
+        setting(&mut env, false, "SYSLOG_RENAME_PROPERTIES", "hostname=host");
+        setting(&mut env, false, "SYSLOG_REMOVE_PROPERTIES", "facility");
+
+        let _ = writeln!(env);
+        let _ = writeln!(env, "# The Seq server to forward events to. These are only used by the");
+        let _ = writeln!(env, "# `datalust/seq-input-syslog` container, which pipes the CLEF that squiflog");
+        let _ = writeln!(env, "# writes to stdout into `seqcli ingest` over HTTP.");
+        setting(&mut env, seq, "SEQ_ADDRESS", &self.seq_address);
+        setting(
+            &mut env,
+            seq && self.api_key.is_some(),
+            "SEQ_API_KEY",
+            self.api_key.as_deref().unwrap_or_default(),
+        );
+
+        let port = self.listen.rsplit(':').next().unwrap_or("514");
+        let run_container = format!(
+            "docker run --rm -it -p {}:{}/udp --env-file {} datalust/seq-input-syslog",
+            port, port, file_name
+        );
+
+        let _ = writeln!(env);
+        match self.scenario {
+            Scenario::Stdout => {
+                let _ = writeln!(env, "# Run the server and write events to a file with:");
+                let _ = writeln!(env, "#   squiflog > events.clef");
+                let _ = writeln!(env, "# or watch them arrive with:");
+                let _ = writeln!(env, "#   squiflog | squiflog tail");
+            }
+            Scenario::Seq => {
+                let _ = writeln!(env, "# Run the container with:");
+                let _ = writeln!(env, "#   {}", run_container);
+            }
+            Scenario::Docker => {
+                let _ = writeln!(env, "# Run the container with:");
+                let _ = writeln!(env, "#   {}", run_container);
+                let _ = writeln!(env, "# then send the logs of other containers to it with:");
+                let _ = writeln!(env, "#   docker run \\");
+                let _ = writeln!(env, "#     --log-driver syslog \\");
+                let _ = writeln!(env, "#     --log-opt syslog-address=udp://<squiflog host>:{} \\", port);
+                let _ = writeln!(env, "#     --log-opt syslog-format=rfc5424 \\");
+                let _ = writeln!(env, "#     my-app:latest");
+            }
+        }
+
+        env
+    }
+}
+
+fn setting(env: &mut String, enabled: bool, name: &str, value: &str) {
+    let _ = writeln!(env, "{}{}={}", if enabled { "" } else { "#" }, name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn settings(scenario: Scenario) -> Settings {
+        Settings {
+            scenario,
+            listen: DEFAULT_LISTEN.to_owned(),
+            seq_address: "https://seq.example.com".to_owned(),
+            api_key: Some("abc123".to_owned()),
+            diagnostics: false,
+        }
+    }
+
+    // The settings that aren't commented out
+    fn enabled(env: &str) -> HashMap<&str, &str> {
+        env.lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.split_once('=').expect("invalid setting"))
+            .collect()
+    }
+
+    #[test]
+    fn render_enables_settings_for_scenario() {
+        let stdout = settings(Scenario::Stdout).render("squiflog.env");
+        assert_eq!(
+            vec![("SYSLOG_ADDRESS", DEFAULT_LISTEN)].into_iter().collect::<HashMap<_, _>>(),
+            enabled(&stdout)
+        );
+        assert!(stdout.contains("#SEQ_ADDRESS=https://seq.example.com\n"));
+        assert!(stdout.contains("#SYSLOG_ENABLE_DIAGNOSTICS=True\n"));
+        assert!(stdout.contains("#SYSLOG_STATS_ADDRESS=127.0.0.1:5140\n"));
+
+        let seq = settings(Scenario::Seq).render("squiflog.env");
+        assert_eq!(
+            vec![
+                ("SYSLOG_ADDRESS", DEFAULT_LISTEN),
+                ("SEQ_ADDRESS", "https://seq.example.com"),
+                ("SEQ_API_KEY", "abc123"),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+            enabled(&seq)
+        );
+    }
+
+    #[test]
+    fn render_docker_shows_logging_driver() {
+        let docker = Settings {
+            listen: "udp://0.0.0.0:1514".to_owned(),
+            ..settings(Scenario::Docker)
+        }
+        .render("syslog.env");
+
+        assert!(docker.contains("-p 1514:1514/udp --env-file syslog.env"));
+        assert!(docker.contains("syslog-address=udp://<squiflog host>:1514"));
+    }
+
+    #[test]
+    fn prompt_uses_defaults_and_arguments() {
+        let args = InitArgs {
+            scenario: None,
+            listen: None,
+            seq_address: None,
+            api_key: Some("abc123".to_owned()),
+            diagnostics: false,
+            output: "-".into(),
+            force: false,
+        };
+
+        let mut input = &b"udp\n3\n\n\ny\n"[..];
+        let settings = prompt(&args, &mut input, &mut io::sink()).expect("failed to prompt");
+
+        assert_eq!(
+            Settings {
+                scenario: Scenario::Docker,
+                listen: DEFAULT_LISTEN.to_owned(),
+                seq_address: DEFAULT_SEQ_ADDRESS.to_owned(),
+                api_key: Some("abc123".to_owned()),
+                diagnostics: true,
+            },
+            settings
+        );
+    }
+
+    #[test]
+    fn validate_rejects_unsupported_listeners() {
+        let err = Settings {
+            listen: "tls://0.0.0.0:6514".to_owned(),
+            ..settings(Scenario::Seq)
+        }
+        .validate()
+        .expect_err("should fail");
+        assert!(err.to_string().contains("TLS input isn't supported"), "{}", err);
+
+        Settings {
+            listen: "tcp://0.0.0.0:514".to_owned(),
+            ..settings(Scenario::Seq)
+        }
+        .validate()
+        .expect_err("should fail");
+    }
+}
@@ -0,0 +1,127 @@
+/*!
+Subcommands for working with SYSLOG and CLEF from the command line.
+*/
+
+use std::{
+    env,
+    ffi::OsString,
+    io::{
+        self,
+        Write,
+    },
+};
+
+use clap::{
+    error::ErrorKind,
+    CommandFactory,
+    Parser,
+};
+
+use squiflog::error::Error;
+
+/**
+Command-line arguments.
+
+This module only depends on `clap`, `clap_complete`, and `std`, and has no inner
+attributes, so the build script can include it to generate man pages and completions.
+*/
+pub mod args;
+
+mod convert;
+mod dashboard;
+mod doctor;
+mod extract_templates;
+mod init;
+mod replay;
+mod tail;
+mod test_rules;
+mod update;
+mod validate_clef;
+
+pub use self::args::{
+    Cli,
+    Command,
+};
+
+/**
+Parse the command line.
+
+The server ignored its arguments before there were subcommands, and the Seq app
+host or wrapper scripts may still pass some. Arguments that don't start with a
+subcommand run the server instead of failing. `--help` and `--version` still work.
+*/
+pub fn parse() -> Cli {
+    parse_from(env::args_os())
+}
+
+fn parse_from(args: impl IntoIterator<Item = OsString>) -> Cli {
+    let args = args.into_iter().collect::<Vec<_>>();
+
+    match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(err) if matches!(err.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => err.exit(),
+        Err(err) if names_command(&args) => err.exit(),
+        Err(_) => Cli { command: None },
+    }
+}
+
+fn names_command(args: &[OsString]) -> bool {
+    let name = match args.get(1).and_then(|arg| arg.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+
+    name == "help"
+        || Cli::command()
+            .get_subcommands()
+            .any(|command| command.get_name() == name || command.get_all_aliases().any(|alias| alias == name))
+}
+
+/**
+Run a subcommand.
+*/
+pub fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Tail(args) => tail::run(args),
+        Command::Convert(args) => convert::run(args),
+        Command::Init(args) => init::run(args),
+        Command::Doctor(args) => doctor::run(args),
+        Command::Replay(args) => replay::run(args),
+        Command::TestRules(args) => test_rules::run(args),
+        Command::ValidateClef(args) => validate_clef::run(args),
+        Command::Dashboard(args) => dashboard::run(args),
+        Command::ExtractTemplates(args) => extract_templates::run(args),
+        Command::Update(args) => update::run(args),
+        Command::Completions(args) => {
+            // Generating straight to stdout panics if it's closed early
+            let mut script = Vec::new();
+            clap_complete::generate(args.shell, &mut Cli::command(), "squiflog", &mut script);
+
+            io::stdout().write_all(&script)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn unknown_arguments_run_the_server() {
+        let parse = |args: &[&str]| parse_from(args.iter().map(OsString::from));
+
+        assert!(parse(&["squiflog"]).command.is_none());
+        assert!(parse(&["squiflog", "--run", "seq-app"]).command.is_none());
+        assert!(parse(&["squiflog", "unknown"]).command.is_none());
+        assert!(matches!(
+            parse(&["squiflog", "validate-clef", "events.clef"]).command,
+            Some(Command::ValidateClef(_))
+        ));
+    }
+}
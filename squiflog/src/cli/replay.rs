@@ -0,0 +1,566 @@
+/*!
+The `replay` subcommand.
+
+Captures are read in the classic pcap format. Only the parts of the link,
+network, and transport layers needed to find UDP payloads are parsed.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{
+        self,
+        BufReader,
+        Read,
+    },
+    thread,
+    time::Instant,
+};
+
+use bytes::Bytes;
+
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc,
+};
+
+use squiflog::{
+    config::Config,
+    data,
+    error::Error,
+};
+
+use super::{
+    args::ReplayArgs,
+    convert,
+};
+
+pub fn run(args: ReplayArgs) -> Result<(), Error> {
+    let config = Config::from_env()?;
+
+    // Captures can be large, so packets are read one at a time
+    let mut capture = Capture::read(BufReader::new(File::open(&args.path)?))?;
+    let link_type = capture.link_type;
+
+    let data = data::Data::new(config.data);
+
+    let mut stats = convert::Stats::default();
+    let mut skipped = BTreeMap::new();
+    let mut packets = 0;
+    let mut clock = Clock::default();
+
+    loop {
+        let packet = match capture.next_packet() {
+            Ok(Some(packet)) => packet,
+            Ok(None) => break,
+            // Captures that were cut short can still be replayed up to the cut
+            Err(err) => {
+                eprintln!("stopped replaying early: {}", err);
+                break;
+            }
+        };
+
+        packets += 1;
+
+        let datagram = match udp(link_type, packet.data) {
+            Ok(datagram) => datagram,
+            Err(reason) => {
+                *skipped.entry(reason).or_insert(0) += 1;
+                continue;
+            }
+        };
+
+        if args.port.is_some_and(|port| port != datagram.dst_port) {
+            *skipped.entry("sent to another port").or_insert(0) += 1;
+            continue;
+        }
+
+        if args.original_timing {
+            clock.wait_until(packet.timestamp);
+        }
+
+        stats.read(
+            &data,
+            Ok(Bytes::copy_from_slice(datagram.payload)),
+            packet.timestamp,
+        );
+    }
+
+    eprintln!("replayed {} packets: {}", packets, stats);
+    for failure in &stats.failures {
+        eprintln!("  {}", failure);
+    }
+    for (reason, count) in skipped {
+        eprintln!("  skipped {} packets: {}", count, reason);
+    }
+
+    Ok(())
+}
+
+/**
+Keeps time between datagrams the same as when they were captured.
+*/
+#[derive(Default)]
+struct Clock {
+    start: Option<(DateTime<Utc>, Instant)>,
+}
+
+impl Clock {
+    fn wait_until(&mut self, timestamp: DateTime<Utc>) {
+        let (first, started) = *self.start.get_or_insert_with(|| (timestamp, Instant::now()));
+
+        // Packets aren't always captured in order, so ones from the past are replayed immediately
+        if let Ok(offset) = (timestamp - first).to_std() {
+            if let Some(wait) = (started + offset).checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+}
+
+// Link types from https://www.tcpdump.org/linktypes.html
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 2] = [0x8100, 0x88a8];
+
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/**
+A capture in the classic pcap format.
+*/
+struct Capture<R> {
+    endian: Endian,
+    nanos: bool,
+    link_type: u32,
+    records: R,
+    // The data of the last packet read
+    buf: Vec<u8>,
+}
+
+/**
+A captured packet.
+*/
+struct Packet<'a> {
+    timestamp: DateTime<Utc>,
+    data: &'a [u8],
+}
+
+/**
+A captured UDP datagram.
+*/
+#[derive(Debug, PartialEq, Eq)]
+struct Datagram<'a> {
+    dst_port: u16,
+    payload: &'a [u8],
+}
+
+impl<R: Read> Capture<R> {
+    /**
+    Read the header of a capture, leaving its packets to be read by `next_packet`.
+    */
+    fn read(mut records: R) -> Result<Self, Error> {
+        let mut header = [0; 24];
+        if read_up_to(&mut records, &mut header)? < header.len() {
+            return Err(Error::msg("the capture is too short to be a pcap file"));
+        }
+
+        let (endian, nanos) = match header[..4] {
+            [0xd4, 0xc3, 0xb2, 0xa1] => (Endian::Little, false),
+            [0xa1, 0xb2, 0xc3, 0xd4] => (Endian::Big, false),
+            [0x4d, 0x3c, 0xb2, 0xa1] => (Endian::Little, true),
+            [0xa1, 0xb2, 0x3c, 0x4d] => (Endian::Big, true),
+            [0x0a, 0x0d, 0x0d, 0x0a] => {
+                return Err(Error::msg(
+                    "the capture is in pcapng format, convert it into pcap using `editcap -F pcap`",
+                ))
+            }
+            _ => return Err(Error::msg("the capture isn't a pcap file")),
+        };
+
+        // The upper bits of the link type are used for other information
+        let link_type = endian.u32(&header[20..24]) & 0x0fff_ffff;
+
+        Ok(Capture {
+            endian,
+            nanos,
+            link_type,
+            records,
+            buf: Vec::new(),
+        })
+    }
+
+    /**
+    Read the next packet, or `None` at the end of the capture.
+    */
+    fn next_packet(&mut self) -> Result<Option<Packet<'_>>, Error> {
+        let mut header = [0; 16];
+        match read_up_to(&mut self.records, &mut header)? {
+            0 => return Ok(None),
+            16 => (),
+            _ => return Err(Error::msg("the last packet header is truncated")),
+        }
+
+        let secs = self.endian.u32(&header[0..4]);
+        let frac = self.endian.u32(&header[4..8]);
+        let len = self.endian.u32(&header[8..12]);
+
+        // The length isn't trusted to allocate up front, in case the capture is corrupt
+        self.buf.clear();
+        (&mut self.records).take(len.into()).read_to_end(&mut self.buf)?;
+        if self.buf.len() < len as usize {
+            return Err(Error::msg("the last packet is truncated"));
+        }
+
+        let nanos = if self.nanos { frac } else { frac.saturating_mul(1000) };
+        let timestamp = Utc
+            .timestamp_opt(secs.into(), nanos)
+            .single()
+            .ok_or_else(|| Error::msg(format!("invalid packet timestamp {}.{}", secs, frac)))?;
+
+        Ok(Some(Packet {
+            timestamp,
+            data: &self.buf,
+        }))
+    }
+}
+
+/**
+Fill `buf` from `reader`, returning fewer bytes than its length only at the end of the input.
+*/
+fn read_up_to(mut reader: impl Read, buf: &mut [u8]) -> Result<usize, io::Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(read)
+}
+
+/**
+Find the UDP datagram in a captured packet.
+
+If there isn't one, the reason the packet was skipped is returned instead.
+*/
+fn udp(link_type: u32, packet: &[u8]) -> Result<Datagram<'_>, &'static str> {
+    let ip = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = be_u16(packet, offset)?;
+            while ETHERTYPE_VLAN.contains(&ethertype) {
+                offset += 4;
+                ethertype = be_u16(packet, offset)?;
+            }
+
+            ip_ethertype(ethertype)?;
+            packet.get(offset + 2..)
+        }
+        LINKTYPE_LINUX_SLL => {
+            ip_ethertype(be_u16(packet, 14)?)?;
+            packet.get(16..)
+        }
+        LINKTYPE_LINUX_SLL2 => {
+            ip_ethertype(be_u16(packet, 0)?)?;
+            packet.get(20..)
+        }
+        // The address family is checked using the IP version instead,
+        // since its value depends on the platform that captured it
+        LINKTYPE_NULL | LINKTYPE_LOOP => packet.get(4..),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(packet),
+        _ => return Err("unsupported link type"),
+    }
+    .ok_or("truncated")?;
+
+    let transport = match ip.first().map(|b| b >> 4) {
+        Some(4) => ipv4(ip)?,
+        Some(6) => ipv6(ip)?,
+        _ => return Err("not IP"),
+    };
+
+    let dst_port = be_u16(transport, 2)?;
+    let len = be_u16(transport, 4)? as usize;
+    if len < 8 {
+        return Err("invalid UDP length");
+    }
+
+    let payload = transport.get(8..len).ok_or("truncated")?;
+
+    Ok(Datagram { dst_port, payload })
+}
+
+fn ipv4(ip: &[u8]) -> Result<&[u8], &'static str> {
+    let header_len = usize::from(ip.first().ok_or("truncated")? & 0x0f) * 4;
+    let total_len = be_u16(ip, 2)? as usize;
+    let fragment = be_u16(ip, 6)?;
+    let protocol = *ip.get(9).ok_or("truncated")?;
+
+    if protocol != IP_PROTOCOL_UDP {
+        return Err("not UDP");
+    }
+
+    // More fragments, or a fragment offset
+    if fragment & 0x3fff != 0 {
+        return Err("fragmented");
+    }
+
+    // Ethernet frames may be padded past the end of the IP packet
+    ip.get(header_len..total_len).ok_or("truncated")
+}
+
+fn ipv6(ip: &[u8]) -> Result<&[u8], &'static str> {
+    let payload_len = be_u16(ip, 4)? as usize;
+    let next_header = *ip.get(6).ok_or("truncated")?;
+
+    // Extension headers aren't followed
+    if next_header != IP_PROTOCOL_UDP {
+        return Err("not UDP");
+    }
+
+    ip.get(40..40 + payload_len).ok_or("truncated")
+}
+
+fn ip_ethertype(ethertype: u16) -> Result<(), &'static str> {
+    match ethertype {
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => Ok(()),
+        _ => Err("not IP"),
+    }
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Result<u16, &'static str> {
+    match bytes.get(at..at + 2) {
+        Some(&[a, b]) => Ok(u16::from_be_bytes([a, b])),
+        _ => Err("truncated"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcap(endian: Endian, link_type: u32, packets: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let u32 = |v: u32| match endian {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        };
+        let u16 = |v: u16| match endian {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        };
+
+        let mut pcap = vec![];
+        pcap.extend_from_slice(&u32(0xa1b2_c3d4));
+        pcap.extend_from_slice(&u16(2));
+        pcap.extend_from_slice(&u16(4));
+        pcap.extend_from_slice(&u32(0));
+        pcap.extend_from_slice(&u32(0));
+        pcap.extend_from_slice(&u32(65535));
+        pcap.extend_from_slice(&u32(link_type));
+
+        for (secs, micros, data) in packets {
+            pcap.extend_from_slice(&u32(*secs));
+            pcap.extend_from_slice(&u32(*micros));
+            pcap.extend_from_slice(&u32(data.len() as u32));
+            pcap.extend_from_slice(&u32(data.len() as u32));
+            pcap.extend_from_slice(data);
+        }
+
+        pcap
+    }
+
+    fn udp_datagram(port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut udp = vec![0xc0, 0x01];
+        udp.extend_from_slice(&port.to_be_bytes());
+        udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(payload);
+        udp
+    }
+
+    fn ipv4_packet(protocol: u8, fragment: u16, transport: &[u8]) -> Vec<u8> {
+        let mut ip = vec![0x45, 0];
+        ip.extend_from_slice(&(20 + transport.len() as u16).to_be_bytes());
+        ip.extend_from_slice(&[0, 0]);
+        ip.extend_from_slice(&fragment.to_be_bytes());
+        ip.extend_from_slice(&[64, protocol, 0, 0, 127, 0, 0, 1, 127, 0, 0, 1]);
+        ip.extend_from_slice(transport);
+        ip
+    }
+
+    fn ethernet_frame(ethertypes: &[u16], ip: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        for (i, ethertype) in ethertypes.iter().enumerate() {
+            frame.extend_from_slice(&ethertype.to_be_bytes());
+            if i + 1 < ethertypes.len() {
+                frame.extend_from_slice(&[0, 1]);
+            }
+        }
+        frame.extend_from_slice(ip);
+
+        // Ethernet frames are padded to a minimum length
+        frame.resize(frame.len().max(60), 0);
+        frame
+    }
+
+    fn packets(bytes: &[u8]) -> Vec<Result<(String, Vec<u8>), Error>> {
+        let mut capture = Capture::read(bytes).expect("invalid capture");
+
+        let mut packets = vec![];
+        loop {
+            match capture.next_packet() {
+                Ok(Some(packet)) => packets.push(Ok((packet.timestamp.to_rfc3339(), packet.data.to_vec()))),
+                Ok(None) => return packets,
+                Err(err) => {
+                    packets.push(Err(err));
+                    return packets;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn capture_reads_packets_in_either_endianness() {
+        for endian in [Endian::Little, Endian::Big] {
+            let bytes = pcap(
+                endian,
+                LINKTYPE_RAW,
+                &[(1581555099, 527825, vec![1, 2, 3]), (1581555100, 0, vec![4])],
+            );
+            let packets = packets(&bytes)
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .expect("invalid packets");
+
+            assert_eq!(2, packets.len());
+            assert_eq!("2020-02-13T00:51:39.527825+00:00", packets[0].0);
+            assert_eq!(vec![1, 2, 3], packets[0].1);
+            assert_eq!(vec![4], packets[1].1);
+        }
+    }
+
+    #[test]
+    fn capture_stops_at_truncated_packet() {
+        let mut bytes = pcap(Endian::Little, LINKTYPE_RAW, &[(0, 0, vec![1, 2, 3]), (0, 0, vec![4, 5, 6])]);
+        bytes.truncate(bytes.len() - 1);
+
+        let truncated_data = packets(&bytes);
+
+        assert_eq!(2, truncated_data.len());
+        assert!(truncated_data[0].is_ok());
+        assert!(truncated_data[1].is_err());
+
+        // Only a partial header is left after the first packet
+        let truncated_header = packets(&bytes[..24 + 16 + 3 + 8]);
+
+        assert_eq!(2, truncated_header.len());
+        assert!(truncated_header[1].is_err());
+    }
+
+    #[test]
+    fn capture_reads_packets_longer_than_the_read_buffer() {
+        let data = (0..20_000).map(|i| i as u8).collect::<Vec<_>>();
+        let bytes = pcap(Endian::Little, LINKTYPE_RAW, &[(0, 0, data.clone()), (0, 0, vec![1])]);
+
+        let mut capture = Capture::read(BufReader::with_capacity(64, &bytes[..])).expect("invalid capture");
+
+        assert_eq!(data, capture.next_packet().expect("invalid packet").expect("missing packet").data);
+        assert_eq!(&[1], capture.next_packet().expect("invalid packet").expect("missing packet").data);
+        assert!(capture.next_packet().expect("invalid packet").is_none());
+    }
+
+    #[test]
+    fn capture_rejects_other_formats() {
+        Capture::read(&[0x0a, 0x0d, 0x0d, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0][..])
+            .err()
+            .expect("should fail");
+        Capture::read(&b"<30>1 - - - - - - not a capture"[..]).err().expect("should fail");
+        Capture::read(&[0xd4, 0xc3, 0xb2, 0xa1][..]).err().expect("should fail");
+    }
+
+    #[test]
+    fn udp_finds_payloads_through_link_layers() {
+        let syslog = b"<30>1 - - - - - - hello world";
+        let ip = ipv4_packet(IP_PROTOCOL_UDP, 0x4000, &udp_datagram(514, syslog));
+
+        let mut sll = vec![0; 14];
+        sll.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        sll.extend_from_slice(&ip);
+
+        let mut null = 2u32.to_le_bytes().to_vec();
+        null.extend_from_slice(&ip);
+
+        let mut ipv6 = vec![0x60, 0, 0, 0];
+        let datagram = udp_datagram(1514, syslog);
+        ipv6.extend_from_slice(&(datagram.len() as u16).to_be_bytes());
+        ipv6.extend_from_slice(&[IP_PROTOCOL_UDP, 64]);
+        ipv6.extend_from_slice(&[0; 32]);
+        ipv6.extend_from_slice(&datagram);
+
+        let cases = [
+            (LINKTYPE_ETHERNET, ethernet_frame(&[ETHERTYPE_IPV4], &ip), 514),
+            (LINKTYPE_ETHERNET, ethernet_frame(&[0x8100, ETHERTYPE_IPV4], &ip), 514),
+            (LINKTYPE_LINUX_SLL, sll, 514),
+            (LINKTYPE_NULL, null, 514),
+            (LINKTYPE_RAW, ipv6, 1514),
+        ];
+
+        for (link_type, packet, dst_port) in cases {
+            assert_eq!(
+                Ok(Datagram {
+                    dst_port,
+                    payload: &syslog[..],
+                }),
+                udp(link_type, &packet)
+            );
+        }
+    }
+
+    #[test]
+    fn udp_skips_other_packets() {
+        let datagram = udp_datagram(514, b"hello");
+
+        let cases = [
+            (ethernet_frame(&[0x0806], &[]), "not IP"),
+            (ethernet_frame(&[ETHERTYPE_IPV4], &ipv4_packet(6, 0, &datagram)), "not UDP"),
+            (
+                ethernet_frame(&[ETHERTYPE_IPV4], &ipv4_packet(IP_PROTOCOL_UDP, 0x2000, &datagram)),
+                "fragmented",
+            ),
+            (vec![0; 10], "truncated"),
+        ];
+
+        for (packet, reason) in cases {
+            assert_eq!(Err(reason), udp(LINKTYPE_ETHERNET, &packet));
+        }
+    }
+}
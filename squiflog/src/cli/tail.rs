@@ -0,0 +1,406 @@
+/*!
+The `tail` subcommand.
+*/
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs::File,
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        IsTerminal,
+        Write,
+    },
+    str::FromStr,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use chrono::{
+    DateTime,
+    Local,
+};
+
+use serde_json::{
+    Map,
+    Value,
+};
+
+use squiflog::{
+    config::Config,
+    data::{
+        self,
+        Output,
+    },
+    diagnostics,
+    error::Error,
+    server::{
+        self,
+        UdpInput,
+    },
+};
+
+use squiflog_core::clef;
+
+use super::args::TailArgs;
+
+// How long to wait before checking a followed file for more events
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+pub fn run(args: TailArgs) -> Result<(), Error> {
+    let tail = Tail {
+        filters: args
+            .filters
+            .iter()
+            .map(|filter| filter.parse())
+            .collect::<Result<_, _>>()?,
+        color: !args.no_color && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal(),
+    };
+
+    match (args.listen, args.path.as_deref()) {
+        (Some(listen), _) => listen_syslog(tail, &listen),
+        (None, None) | (None, Some("-")) => read_clef(tail, io::stdin().lock(), false),
+        (None, Some(path)) => read_clef(tail, BufReader::new(File::open(path)?), args.follow),
+    }
+}
+
+/**
+Show events from a stream of newline-delimited CLEF.
+*/
+fn read_clef(tail: Tail, mut reader: impl BufRead, follow: bool) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+
+    let mut line = String::new();
+    let mut line_number = 0;
+    loop {
+        let read = reader.read_line(&mut line)?;
+
+        // Wait for the rest of the line to be written
+        if follow && !line.ends_with('\n') {
+            thread::sleep(FOLLOW_INTERVAL);
+            continue;
+        }
+
+        if read == 0 {
+            return Ok(());
+        }
+
+        line_number += 1;
+
+        let clef = line.trim();
+        if !clef.is_empty() {
+            match serde_json::from_str::<Value>(clef) {
+                Ok(evt) => {
+                    if let Some(rendered) = tail.render(&evt) {
+                        if !write_line(&mut stdout, &rendered)? {
+                            return Ok(());
+                        }
+                    }
+                }
+                Err(err) => eprintln!("skipping invalid CLEF on line {}: {}", line_number, err),
+            }
+        }
+
+        line.clear();
+    }
+}
+
+/**
+Show events converted from SYSLOG messages received on an address.
+
+The address is bound here rather than shared with a running server, so this
+takes the server's place while it's running.
+*/
+fn listen_syslog(tail: Tail, listen: &str) -> Result<(), Error> {
+    let config = Config::from_env()?;
+    let bind: server::Bind = listen.parse()?;
+
+    diagnostics::init(config.diagnostics);
+
+    let handle = Arc::new(Mutex::new(None));
+
+    let process = {
+        let data = data::Data::builder()
+            .config(config.data)
+            .output(Render {
+                tail,
+                server: handle.clone(),
+            })
+            .build();
+
+        move |msg: bytes::Bytes| data.read_as_clef(&msg).map(|_| ())
+    };
+
+    let mut server = server::build_with(UdpInput::new(bind.addr.parse()?), process)?;
+    *handle.lock().expect("failed to lock server handle") = server.take_handle();
+
+    server.run()?;
+    diagnostics::stop()?;
+
+    Ok(())
+}
+
+/**
+An output that shows events on stdout.
+
+The server is closed once stdout is, like when piped into `head`.
+*/
+struct Render {
+    tail: Tail,
+    server: Arc<Mutex<Option<server::Handle>>>,
+}
+
+impl Output for Render {
+    fn emit(&self, evt: &clef::Message) -> Result<(), Error> {
+        if let Some(rendered) = self.tail.render(&serde_json::to_value(evt)?) {
+            if !write_line(&mut io::stdout().lock(), &rendered)? {
+                if let Some(server) = self.server.lock().expect("failed to lock server handle").take() {
+                    server.close();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Returns `false` if stdout has been closed, like when piped into `head`
+fn write_line(stdout: &mut impl Write, line: &str) -> Result<bool, Error> {
+    match writeln!(stdout, "{}", line).and_then(|_| stdout.flush()) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+struct Tail {
+    filters: Vec<Filter>,
+    color: bool,
+}
+
+impl Tail {
+    /**
+    Render an event as a line of text, if it matches the filters.
+    */
+    fn render(&self, evt: &Value) -> Option<String> {
+        let evt = evt.as_object()?;
+
+        if !self.filters.iter().all(|filter| filter.matches(evt)) {
+            return None;
+        }
+
+        let timestamp = evt.get("@t").and_then(Value::as_str).unwrap_or_default();
+        let timestamp = match DateTime::parse_from_rfc3339(timestamp) {
+            Ok(ts) => ts.with_timezone(&Local).format("%H:%M:%S%.3f").to_string(),
+            Err(_) => timestamp.to_owned(),
+        };
+
+        let (level, level_color) = level(evt.get("@l").and_then(Value::as_str).unwrap_or("info"));
+
+        let message = evt
+            .get("@m")
+            .or_else(|| evt.get("@mt"))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        let mut rendered = format!("{} {} {}", timestamp, self.paint(level_color, &format!("[{}]", level)), message);
+
+        let properties = evt
+            .iter()
+            .filter(|(name, _)| !name.starts_with('@') || name.starts_with("@@"))
+            .fold(String::new(), |mut properties, (name, value)| {
+                let _ = write!(properties, " {}={}", name, value);
+                properties
+            });
+        if !properties.is_empty() {
+            rendered.push_str(&self.paint(GRAY, &properties));
+        }
+
+        if let Some(exception) = evt.get("@x").and_then(Value::as_str) {
+            rendered.push('\n');
+            rendered.push_str(&self.paint(RED, exception));
+        }
+
+        Some(rendered)
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color && !color.is_empty() {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const GRAY: &str = "\x1b[90m";
+const RED: &str = "\x1b[31m";
+
+// Map both SYSLOG severities and Seq levels onto short names
+fn level(level: &str) -> (String, &'static str) {
+    match &*level.to_lowercase() {
+        "emerg" | "alert" | "crit" | "fatal" | "critical" => ("FTL".to_owned(), "\x1b[1;37;41m"),
+        "err" | "error" => ("ERR".to_owned(), "\x1b[1;31m"),
+        "warning" | "warn" => ("WRN".to_owned(), "\x1b[1;33m"),
+        "notice" | "info" | "information" => ("INF".to_owned(), "\x1b[36m"),
+        "debug" | "verbose" | "trace" => ("DBG".to_owned(), GRAY),
+        other => (other.chars().take(3).collect::<String>().to_uppercase(), ""),
+    }
+}
+
+/**
+A filter on the properties of an event.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Filter {
+    name: String,
+    op: Op,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Op {
+    Exists,
+    Eq(String),
+    NotEq(String),
+    Contains(String),
+}
+
+impl Filter {
+    fn matches(&self, evt: &Map<String, Value>) -> bool {
+        let value = evt.get(&self.name).map(|value| match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        });
+
+        match (&self.op, value) {
+            (Op::Exists, value) => value.is_some(),
+            (Op::Eq(expected), Some(value)) => *expected == value,
+            (Op::NotEq(expected), Some(value)) => *expected != value,
+            (Op::NotEq(_), None) => true,
+            (Op::Contains(expected), Some(value)) => value.contains(&**expected),
+            (Op::Eq(_), None) | (Op::Contains(_), None) => false,
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, op) = match s.find(['=', '~', '!']) {
+            None => (s, Op::Exists),
+            Some(i) => {
+                let (name, rest) = s.split_at(i);
+
+                if let Some(value) = rest.strip_prefix("!=") {
+                    (name, Op::NotEq(value.to_owned()))
+                } else if let Some(value) = rest.strip_prefix('=') {
+                    (name, Op::Eq(value.to_owned()))
+                } else if let Some(value) = rest.strip_prefix('~') {
+                    (name, Op::Contains(value.to_owned()))
+                } else {
+                    return Err(Error::msg(format!("invalid filter `{}`, expected `!=`", s)));
+                }
+            }
+        };
+
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::msg(format!("invalid filter `{}`, expected a property name", s)));
+        }
+
+        Ok(Filter {
+            name: name.to_owned(),
+            op,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tail(filters: &[&str]) -> Tail {
+        Tail {
+            filters: filters.iter().map(|f| f.parse().expect("invalid filter")).collect(),
+            color: false,
+        }
+    }
+
+    #[test]
+    fn filters_are_parsed() {
+        let cases = [
+            ("hostname", Op::Exists),
+            ("hostname=web-1", Op::Eq("web-1".to_owned())),
+            ("hostname!=web-1", Op::NotEq("web-1".to_owned())),
+            ("@m~failed", Op::Contains("failed".to_owned())),
+            ("expr=a!=b", Op::Eq("a!=b".to_owned())),
+        ];
+
+        for (filter, op) in cases {
+            let filter: Filter = filter.parse().expect("invalid filter");
+            assert_eq!(op, filter.op);
+        }
+
+        "=web-1".parse::<Filter>().expect_err("should fail");
+        "hostname!web-1".parse::<Filter>().expect_err("should fail");
+    }
+
+    #[test]
+    fn render_applies_filters() {
+        let evt = json!({
+            "@t": "2020-02-13T00:51:39.527825Z",
+            "@l": "err",
+            "@m": "disk failed",
+            "hostname": "web-1",
+            "proc_id": 1481,
+        });
+
+        assert!(tail(&["hostname=web-1", "@m~failed"]).render(&evt).is_some());
+        assert!(tail(&["proc_id=1481"]).render(&evt).is_some());
+        assert!(tail(&["app_name!=web"]).render(&evt).is_some());
+        assert!(tail(&["hostname!=web-1"]).render(&evt).is_none());
+        assert!(tail(&["app_name"]).render(&evt).is_none());
+    }
+
+    #[test]
+    fn render_shows_level_message_and_properties() {
+        let evt = json!({
+            "@t": "not a timestamp",
+            "@l": "warning",
+            "@m": "disk almost full",
+            "@x": "details",
+            "hostname": "web-1",
+            "@@l": "escaped",
+        });
+
+        let rendered = tail(&[]).render(&evt).expect("event should render");
+
+        assert_eq!(
+            "not a timestamp [WRN] disk almost full @@l=\"escaped\" hostname=\"web-1\"\ndetails",
+            rendered
+        );
+    }
+
+    #[test]
+    fn render_colors_levels() {
+        let evt = json!({ "@t": "", "@l": "err", "@m": "failed" });
+
+        let rendered = Tail {
+            filters: vec![],
+            color: true,
+        }
+        .render(&evt)
+        .expect("event should render");
+
+        assert!(rendered.contains("\x1b[1;31m[ERR]\x1b[0m"), "{:?}", rendered);
+    }
+}
@@ -0,0 +1,295 @@
+/*!
+The `test-rules` subcommand.
+*/
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        Write,
+    },
+    path::Path,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+use serde_json::Value;
+
+use squiflog::{
+    config::Config,
+    data::{
+        self,
+        Output,
+        Step,
+    },
+    error::Error,
+};
+
+use squiflog_core::clef;
+
+use super::args::TestRulesArgs;
+
+pub fn run(args: TestRulesArgs) -> Result<(), Error> {
+    if let Some(env_file) = &args.env_file {
+        load_env_file(env_file)?;
+    }
+
+    let config = Config::from_env()?;
+    let rules = Rules::new(config.data);
+
+    let messages = if !args.messages.is_empty() {
+        args.messages.into_iter().map(String::into_bytes).collect()
+    } else {
+        match args.path.as_deref() {
+            None => read_messages(io::stdin().lock())?,
+            Some(path) if path == Path::new("-") => read_messages(io::stdin().lock())?,
+            Some(path) => read_messages(BufReader::new(File::open(path)?))?,
+        }
+    };
+
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "{}", rules.describe())?;
+
+    for (i, msg) in messages.iter().enumerate() {
+        write!(stdout, "\n{}", rules.explain(i + 1, msg, Utc::now()))?;
+    }
+
+    Ok(())
+}
+
+/**
+Set environment variables from an env file.
+
+Lines are `NAME=value`, with blank lines and `#` comments ignored, like `docker run --env-file`.
+*/
+fn load_env_file(path: &Path) -> Result<(), Error> {
+    for (name, value) in parse_env_file(&fs::read_to_string(path)?) {
+        env::set_var(name, value);
+    }
+
+    Ok(())
+}
+
+fn parse_env_file(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim(), value))
+        .collect()
+}
+
+fn read_messages(reader: impl BufRead) -> Result<Vec<Vec<u8>>, Error> {
+    let mut messages = Vec::new();
+
+    for line in reader.split(b'\n') {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        if !line.is_empty() {
+            messages.push(line);
+        }
+    }
+
+    Ok(messages)
+}
+
+/**
+The configured rules, traced through [`data::Data`] so their effects can be seen.
+*/
+struct Rules {
+    config: data::Config,
+    data: data::Data,
+    emitted: Capture,
+}
+
+impl Rules {
+    fn new(config: data::Config) -> Self {
+        let emitted = Capture::default();
+        let data = data::Data::builder()
+            .config(config.clone())
+            .output(emitted.clone())
+            .build();
+
+        Rules { config, data, emitted }
+    }
+
+    fn describe(&self) -> String {
+        let mut description = String::new();
+
+        let _ = writeln!(description, "strictness: {:?}", self.config.strictness);
+        let _ = writeln!(description, "precedence: {:?}", self.config.precedence);
+
+        let rules = self.config.enrichment_rules();
+        if rules.is_empty() {
+            let _ = writeln!(description, "rules: none");
+        } else {
+            let _ = writeln!(description, "rules:");
+            for rule in rules {
+                let _ = writeln!(description, "  {}", rule);
+            }
+        }
+
+        description
+    }
+
+    fn explain(&self, n: usize, msg: &[u8], received: DateTime<Utc>) -> String {
+        let mut explanation = String::new();
+
+        let _ = writeln!(explanation, "message {}: {}", n, String::from_utf8_lossy(msg));
+
+        let mut previous = None;
+        let traced = self.data.trace_as_clef_at(msg, received, |step| match step {
+            Step::Parsed { format, fallback, evt } => {
+                match fallback {
+                    Some(reason) => {
+                        let _ = writeln!(explanation, "  format: {}, because it isn't RFC 5424: {}", format, reason);
+                    }
+                    None => {
+                        let _ = writeln!(explanation, "  format: {}", format);
+                    }
+                }
+                previous = serde_json::to_value(evt).ok();
+            }
+            Step::Enriched { rule, evt } => {
+                let after = serde_json::to_value(evt).ok();
+
+                if let Some(rule) = rule {
+                    let outcome = if previous == after { "unchanged" } else { "matched" };
+                    let _ = writeln!(explanation, "  {}: {}", outcome, rule);
+                }
+
+                previous = after;
+            }
+        });
+
+        if let Err(err) = traced {
+            let _ = writeln!(explanation, "  rejected: {}", err);
+            return explanation;
+        }
+
+        match self.emitted.take() {
+            Some(clef) => {
+                let _ = writeln!(explanation, "  clef: {}", clef);
+            }
+            None => {
+                let _ = writeln!(explanation, "  nothing was emitted");
+            }
+        }
+
+        explanation
+    }
+}
+
+/**
+An output that keeps the last event instead of writing it.
+
+Going through a `Value` sorts properties, so events are easier to compare.
+*/
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Option<Value>>>);
+
+impl Capture {
+    fn take(&self) -> Option<Value> {
+        self.0.lock().expect("failed to lock output").take()
+    }
+}
+
+impl Output for Capture {
+    fn emit(&self, evt: &clef::Message) -> Result<(), Error> {
+        *self.0.lock().expect("failed to lock output") = Some(serde_json::to_value(evt)?);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squiflog::data::Rule;
+
+    fn rules(strictness: data::Strictness) -> Rules {
+        Rules::new(data::Config {
+            strictness,
+            properties: vec![("environment".to_owned(), "test".into())],
+            rules: vec![
+                Rule::Rename {
+                    from: "hostname".to_owned(),
+                    to: "host".to_owned(),
+                },
+                Rule::Remove {
+                    name: "proc_id".to_owned(),
+                },
+            ],
+            ..data::Config::default()
+        })
+    }
+
+    #[test]
+    fn explain_shows_matched_rules_and_clef() {
+        let explanation = rules(data::Strictness::Lenient).explain(
+            1,
+            b"<30>1 2020-02-13T00:51:39Z docker-desktop app - - - hello world",
+            Utc::now(),
+        );
+
+        let lines = explanation.lines().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "message 1: <30>1 2020-02-13T00:51:39Z docker-desktop app - - - hello world",
+                "  format: RFC 5424",
+                "  matched: property `environment` = \"test\"",
+                "  matched: rename `hostname` to `host`",
+                "  unchanged: remove `proc_id`",
+            ],
+            lines[..5]
+        );
+
+        let clef: Value = serde_json::from_str(lines[5].trim_start_matches("  clef: ")).expect("invalid CLEF");
+        assert_eq!("docker-desktop", clef["host"]);
+        assert_eq!("test", clef["environment"]);
+    }
+
+    #[test]
+    fn explain_shows_format_decisions() {
+        let msg = b"<34>Oct 11 22:14:15 mymachine su: 'su root' failed";
+
+        let lenient = rules(data::Strictness::Lenient).explain(1, msg, Utc::now());
+        assert!(
+            lenient.contains("  format: RFC 3164, because it isn't RFC 5424: invalid header at byte 4"),
+            "{}",
+            lenient
+        );
+
+        let strict = rules(data::Strictness::Rfc5424).explain(1, msg, Utc::now());
+        assert!(strict.contains("  rejected: invalid header at byte 4"), "{}", strict);
+        assert!(!strict.contains("clef:"), "{}", strict);
+    }
+
+    #[test]
+    fn env_files_are_parsed() {
+        let env = "# comment\n\nSYSLOG_ADDRESS=udp://0.0.0.0:514\n#SEQ_API_KEY=\nSYSLOG_PROPERTIES=a=b\nINHERITED\n";
+
+        assert_eq!(
+            vec![("SYSLOG_ADDRESS", "udp://0.0.0.0:514"), ("SYSLOG_PROPERTIES", "a=b")],
+            parse_env_file(env)
+        );
+    }
+}
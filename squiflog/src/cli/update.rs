@@ -0,0 +1,340 @@
+/*!
+The `update` subcommand.
+
+Releases are found using the GitHub releases API. Each release has a binary for
+each target, named like `squiflog-x86_64-unknown-linux-musl`, with an Ed25519
+signature alongside, named like `squiflog-x86_64-unknown-linux-musl.sig`. The
+signature covers the release version and target as well as the binary, so an
+older binary can't be installed as a newer release. Binaries are signed by the
+`sign-release` example.
+
+Release builds set these environment variables:
+
+- `SQUIFLOG_VERSION`: the version to compare releases with.
+- `SQUIFLOG_UPDATE_PUBLIC_KEY`: the hex-encoded Ed25519 public key binaries are signed with.
+  Builds without it can check for updates, but won't install them.
+*/
+
+use std::{
+    env,
+    fs,
+    io::Read,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Duration,
+};
+
+use ed25519_dalek::{
+    Signature,
+    VerifyingKey,
+};
+
+use semver::Version;
+
+use serde_derive::Deserialize;
+
+use squiflog::error::Error;
+
+use super::args::UpdateArgs;
+
+mod signing;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/datalust/squiflog/releases/latest";
+
+const VERSION: &str = match option_env!("SQUIFLOG_VERSION") {
+    Some(version) => version,
+    None => env!("CARGO_PKG_VERSION"),
+};
+
+const PUBLIC_KEY: Option<&str> = option_env!("SQUIFLOG_UPDATE_PUBLIC_KEY");
+
+// Set by the build script
+const TARGET: &str = env!("SQUIFLOG_TARGET");
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+// Binaries are a few MB, so anything much bigger isn't one
+const MAX_DOWNLOAD_SIZE: u64 = 256 * 1024 * 1024;
+
+pub fn run(args: UpdateArgs) -> Result<(), Error> {
+    let current = parse_version(VERSION)?;
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(&format!("squiflog/{}", current))
+        .build();
+
+    let url = args.url.as_deref().unwrap_or(LATEST_RELEASE_URL);
+    let release = Release::parse(&download(&agent, url)?)?;
+
+    if release.version <= current {
+        println!("squiflog {} is up to date", current);
+        return Ok(());
+    }
+
+    println!("squiflog {} is available, this is {}", release.version, current);
+
+    if args.check_only {
+        return Ok(());
+    }
+
+    // Check everything needed to install the update before downloading it
+    let key = PUBLIC_KEY
+        .ok_or_else(|| {
+            Error::msg("this build can't verify updates because it doesn't include a public key, update it the same way it was installed instead")
+        })
+        .and_then(parse_public_key)?;
+    let (binary_url, signature_url) = release.artifact(&artifact_name())?;
+    let exe = env::current_exe()?;
+
+    let binary = download(&agent, binary_url)?;
+    let signature = download(&agent, signature_url)?;
+    verify(&key, &release.version, &binary, &signature)?;
+
+    replace(&exe, &binary)?;
+
+    println!("updated `{}` to squiflog {}", exe.display(), release.version);
+
+    Ok(())
+}
+
+fn download(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>, Error> {
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|err| Error::msg(format!("failed to download {}", err)))?;
+
+    let mut body = Vec::new();
+    response.into_reader().take(MAX_DOWNLOAD_SIZE + 1).read_to_end(&mut body)?;
+
+    if body.len() as u64 > MAX_DOWNLOAD_SIZE {
+        return Err(Error::msg(format!("`{}` is too large to be a release", url)));
+    }
+
+    Ok(body)
+}
+
+fn artifact_name() -> String {
+    format!("squiflog-{}{}", TARGET, env::consts::EXE_SUFFIX)
+}
+
+fn parse_version(version: &str) -> Result<Version, Error> {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+
+    Version::parse(version).map_err(|err| Error::msg(format!("invalid version `{}`: {}", version, err)))
+}
+
+fn parse_public_key(hex: &str) -> Result<VerifyingKey, Error> {
+    let key = signing::parse_key(hex).ok_or_else(|| Error::msg("invalid public key, expected 64 hex characters"))?;
+
+    VerifyingKey::from_bytes(&key).map_err(|err| Error::msg(format!("invalid public key: {}", err)))
+}
+
+/**
+Verify that a binary was signed as the given release for this target.
+*/
+fn verify(key: &VerifyingKey, version: &Version, binary: &[u8], signature: &[u8]) -> Result<(), Error> {
+    let signature = Signature::from_slice(signature)
+        .map_err(|_| Error::msg("the update's signature is invalid, expected 64 bytes"))?;
+
+    key.verify_strict(&signing::payload(&version.to_string(), TARGET, binary), &signature)
+        .map_err(|_| {
+            Error::msg(format!(
+                "the update's signature doesn't match squiflog {} for {}, so it wasn't installed",
+                version, TARGET
+            ))
+        })
+}
+
+/**
+Replace a binary with a new one.
+
+The new binary is written alongside the old one and renamed over it, so a failure
+part way through doesn't leave a broken binary behind. Windows doesn't allow running
+binaries to be replaced, but does allow them to be renamed, so the old binary is
+moved out of the way first.
+*/
+fn replace(exe: &Path, binary: &[u8]) -> Result<(), Error> {
+    let staged = sibling(exe, "update")?;
+    let old = sibling(exe, "old")?;
+
+    fs::write(&staged, binary)?;
+
+    let replaced = fs::metadata(exe)
+        .and_then(|metadata| fs::set_permissions(&staged, metadata.permissions()))
+        .and_then(|_| {
+            if !cfg!(windows) {
+                return fs::rename(&staged, exe);
+            }
+
+            let _ = fs::remove_file(&old);
+            fs::rename(exe, &old)?;
+
+            // Put the old binary back so there's still one to run
+            fs::rename(&staged, exe).inspect_err(|_| {
+                let _ = fs::rename(&old, exe);
+            })
+        });
+
+    if let Err(err) = replaced {
+        let _ = fs::remove_file(&staged);
+        return Err(Error::msg(format!("failed to replace `{}`: {}", exe.display(), err)));
+    }
+
+    Ok(())
+}
+
+// A hidden file next to the binary, like `.squiflog.update`
+fn sibling(exe: &Path, extension: &str) -> Result<PathBuf, Error> {
+    let name = exe
+        .file_name()
+        .ok_or_else(|| Error::msg(format!("`{}` isn't a file", exe.display())))?;
+
+    Ok(exe.with_file_name(format!(".{}.{}", name.to_string_lossy(), extension)))
+}
+
+/**
+The latest release, as returned by the GitHub releases API.
+*/
+#[derive(Debug)]
+struct Release {
+    version: Version,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn parse(json: &[u8]) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct Latest {
+            tag_name: String,
+            assets: Vec<Asset>,
+        }
+
+        let latest: Latest =
+            serde_json::from_slice(json).map_err(|err| Error::msg(format!("invalid release: {}", err)))?;
+
+        Ok(Release {
+            version: parse_version(&latest.tag_name)?,
+            assets: latest.assets,
+        })
+    }
+
+    /**
+    The URLs of a binary and its signature.
+    */
+    fn artifact(&self, name: &str) -> Result<(&str, &str), Error> {
+        let url = |name: &str| {
+            self.assets
+                .iter()
+                .find(|asset| asset.name == name)
+                .map(|asset| &*asset.browser_download_url)
+        };
+
+        match (url(name), url(&format!("{}.sig", name))) {
+            (Some(binary), Some(signature)) => Ok((binary, signature)),
+            (Some(_), None) => Err(Error::msg(format!(
+                "squiflog {} doesn't have a signature for `{}`, so it can't be installed",
+                self.version, name
+            ))),
+            (None, _) => Err(Error::msg(format!(
+                "squiflog {} doesn't have a `{}` binary",
+                self.version, name
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{
+        Signer,
+        SigningKey,
+    };
+    use std::process;
+
+    #[test]
+    fn releases_are_parsed() {
+        let release = Release::parse(
+            br#"{
+                "tag_name": "v1.0.123",
+                "name": "1.0.123",
+                "assets": [
+                    { "name": "squiflog-x86_64-unknown-linux-musl", "browser_download_url": "https://example.com/squiflog" },
+                    { "name": "squiflog-x86_64-unknown-linux-musl.sig", "browser_download_url": "https://example.com/squiflog.sig" },
+                    { "name": "squiflog-x86_64-pc-windows-msvc.exe", "browser_download_url": "https://example.com/squiflog.exe" }
+                ]
+            }"#,
+        )
+        .expect("invalid release");
+
+        assert_eq!(Version::new(1, 0, 123), release.version);
+        assert!(release.version > parse_version("1.0.99-dev").expect("invalid version"));
+
+        assert_eq!(
+            ("https://example.com/squiflog", "https://example.com/squiflog.sig"),
+            release.artifact("squiflog-x86_64-unknown-linux-musl").expect("missing artifact")
+        );
+        assert_eq!(
+            "squiflog 1.0.123 doesn't have a signature for `squiflog-x86_64-pc-windows-msvc.exe`, so it can't be installed",
+            release.artifact("squiflog-x86_64-pc-windows-msvc.exe").unwrap_err().to_string()
+        );
+        release.artifact("squiflog-aarch64-apple-darwin").expect_err("should fail");
+    }
+
+    #[test]
+    fn signatures_are_verified() {
+        let secret = SigningKey::from_bytes(&[7; 32]);
+        let hex = secret
+            .verifying_key()
+            .as_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        let key = parse_public_key(&hex).expect("invalid key");
+
+        let version = Version::new(1, 0, 123);
+        let binary = b"a squiflog binary";
+        let signature = secret.sign(&signing::payload("1.0.123", TARGET, binary)).to_bytes();
+
+        verify(&key, &version, binary, &signature).expect("signature should verify");
+        verify(&key, &version, b"a tampered binary", &signature).expect_err("should fail");
+        verify(&key, &version, binary, &signature[..32]).expect_err("should fail");
+
+        // An older release can't be served as a newer one
+        verify(&key, &Version::new(1, 0, 124), binary, &signature).expect_err("should fail");
+
+        // Or a release for another target
+        let other = secret.sign(&signing::payload("1.0.123", "wasm32-unknown-unknown", binary)).to_bytes();
+        verify(&key, &version, binary, &other).expect_err("should fail");
+
+        parse_public_key(&hex[..62]).expect_err("should fail");
+        parse_public_key(&hex.replace(|c: char| c.is_ascii_digit(), "z")).expect_err("should fail");
+    }
+
+    #[test]
+    fn binaries_are_replaced() {
+        let dir = env::temp_dir().join(format!("squiflog-update-{}", process::id()));
+        fs::create_dir_all(&dir).expect("failed to create dir");
+
+        let exe = dir.join("squiflog");
+        fs::write(&exe, b"old").expect("failed to write binary");
+
+        replace(&exe, b"new").expect("failed to replace binary");
+
+        assert_eq!(b"new", &*fs::read(&exe).expect("failed to read binary"));
+        assert!(!dir.join(".squiflog.update").exists());
+
+        fs::remove_dir_all(&dir).expect("failed to remove dir");
+    }
+}
@@ -0,0 +1,37 @@
+// How release binaries are signed.
+//
+// This file has no inner attributes, so the `sign-release` example can include it
+// to sign binaries in the same way they're verified.
+
+/**
+The bytes that are signed for a release binary.
+
+The version and target are signed along with the binary, so a signed binary
+can't be passed off as a different release, or a build for another platform.
+*/
+pub fn payload(version: &str, target: &str, binary: &[u8]) -> Vec<u8> {
+    let header = format!("squiflog-update\n{}\n{}\n", version, target);
+
+    let mut payload = Vec::with_capacity(header.len() + binary.len());
+    payload.extend_from_slice(header.as_bytes());
+    payload.extend_from_slice(binary);
+
+    payload
+}
+
+/**
+Parse a hex-encoded Ed25519 key.
+*/
+pub fn parse_key(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(key)
+}
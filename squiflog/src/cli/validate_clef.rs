@@ -0,0 +1,282 @@
+/*!
+The `validate-clef` subcommand.
+
+Events are checked against the reified properties described by the CLEF format
+at https://clef-json.org, and the ones Seq adds for traces.
+*/
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::File,
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        Write,
+    },
+    path::Path,
+};
+
+use chrono::DateTime;
+
+use serde::de::{
+    Deserialize,
+    Deserializer,
+    MapAccess,
+    Visitor,
+};
+
+use serde_json::Value;
+
+use squiflog::error::Error;
+
+use super::args::ValidateClefArgs;
+
+pub fn run(args: ValidateClefArgs) -> Result<(), Error> {
+    match args.path.as_deref() {
+        None => validate_stream(io::stdin().lock()),
+        Some(path) if path == Path::new("-") => validate_stream(io::stdin().lock()),
+        Some(path) => validate_stream(BufReader::new(File::open(path)?)),
+    }
+}
+
+fn validate_stream(reader: impl BufRead) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+
+    let mut events = 0;
+    let mut invalid = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        events += 1;
+
+        let problems = validate(&line);
+        if !problems.is_empty() {
+            invalid += 1;
+        }
+
+        for problem in problems {
+            writeln!(stdout, "line {}: {}", i + 1, problem)?;
+        }
+    }
+
+    eprintln!("checked {} events, {} invalid", events, invalid);
+
+    if invalid > 0 {
+        return Err(Error::msg(format!("{} of {} events are invalid", invalid, events)));
+    }
+
+    Ok(())
+}
+
+/**
+Find the problems with a single CLEF event.
+*/
+fn validate(line: &str) -> Vec<String> {
+    let Event(properties) = match serde_json::from_str(line) {
+        Ok(event) => event,
+        Err(err) => return vec![format!("invalid JSON: {}", err)],
+    };
+
+    let mut problems = Vec::new();
+
+    let mut seen = HashSet::new();
+    for (name, _) in &properties {
+        if !seen.insert(&**name) {
+            problems.push(format!("`{}` appears more than once", name));
+        }
+    }
+
+    let get = |name: &str| properties.iter().find(|(n, _)| n == name).map(|(_, value)| value);
+
+    match get("@t") {
+        Some(value) => check_timestamp("@t", value, &mut problems),
+        None => problems.push("missing a `@t` timestamp".to_owned()),
+    }
+
+    if get("@r").is_some() && get("@mt").is_none() {
+        problems.push("`@r` renderings need a `@mt` message template".to_owned());
+    }
+
+    for (name, value) in &properties {
+        // Names starting with `@@` are escaped user properties
+        if !name.starts_with('@') || name.starts_with("@@") {
+            continue;
+        }
+
+        match &**name {
+            "@t" => (),
+            "@st" => check_timestamp(name, value, &mut problems),
+            "@m" | "@mt" | "@l" | "@x" | "@tr" | "@sp" | "@ps" | "@sk" => {
+                check_type(name, value, Kind::String, &mut problems)
+            }
+            "@i" => match value {
+                Value::String(_) => (),
+                Value::Number(n) if n.as_u64().is_some_and(|n| n <= u64::from(u32::MAX)) => (),
+                _ => problems.push(format!(
+                    "`@i` should be a hex string or 32-bit unsigned number, but is {}",
+                    Kind::of(value)
+                )),
+            },
+            "@r" => match value {
+                Value::Array(renderings) if renderings.iter().all(Value::is_string) => (),
+                _ => problems.push("`@r` should be an array of strings".to_owned()),
+            },
+            "@ra" | "@sa" => check_type(name, value, Kind::Object, &mut problems),
+            _ => problems.push(format!(
+                "`{}` isn't a reified property, user properties starting with `@` need to be escaped as `@{}`",
+                name, name
+            )),
+        }
+    }
+
+    problems
+}
+
+fn check_timestamp(name: &str, value: &Value, problems: &mut Vec<String>) {
+    match value.as_str() {
+        Some(timestamp) => {
+            if let Err(err) = DateTime::parse_from_rfc3339(timestamp) {
+                problems.push(format!(
+                    "`{}` should be an ISO 8601 timestamp with an offset, but `{}` is invalid: {}",
+                    name, timestamp, err
+                ));
+            }
+        }
+        None => check_type(name, value, Kind::String, problems),
+    }
+}
+
+fn check_type(name: &str, value: &Value, expected: Kind, problems: &mut Vec<String>) {
+    let actual = Kind::of(value);
+    if actual != expected {
+        problems.push(format!("`{}` should be {}, but is {}", name, expected, actual));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Kind::Null,
+            Value::Bool(_) => Kind::Bool,
+            Value::Number(_) => Kind::Number,
+            Value::String(_) => Kind::String,
+            Value::Array(_) => Kind::Array,
+            Value::Object(_) => Kind::Object,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Null => "null",
+            Kind::Bool => "a boolean",
+            Kind::Number => "a number",
+            Kind::String => "a string",
+            Kind::Array => "an array",
+            Kind::Object => "an object",
+        })
+    }
+}
+
+/**
+The properties of an event in the order they appear.
+
+Unlike a `serde_json::Map`, duplicate names are kept so they can be reported.
+*/
+struct Event(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct EventVisitor;
+
+        impl<'de> Visitor<'de> for EventVisitor {
+            type Value = Event;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut properties = Vec::with_capacity(map.size_hint().unwrap_or_default());
+                while let Some(property) = map.next_entry()? {
+                    properties.push(property);
+                }
+
+                Ok(Event(properties))
+            }
+        }
+
+        deserializer.deserialize_map(EventVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_events_have_no_problems() {
+        let events = [
+            r#"{"@t":"2020-02-13T00:51:39.527825Z","@m":"hello world","@l":"info","hostname":"web-1"}"#,
+            r#"{"@t":"2020-02-13T00:51:39+10:00","@mt":"{A} failed","@r":["1"],"@i":"a1b2c3d4","@@t":"escaped","A":1}"#,
+            r#"{"@t":"2020-02-13T00:51:39Z","@st":"2020-02-13T00:51:38Z","@tr":"abc","@sp":"def","@i":17,"@ra":{}}"#,
+        ];
+
+        for event in events {
+            assert_eq!(Vec::<String>::new(), validate(event), "{}", event);
+        }
+    }
+
+    #[test]
+    fn invalid_events_have_problems() {
+        let cases = [
+            (r#"["not", "an", "event"]"#, "invalid JSON: invalid type: sequence, expected a JSON object"),
+            (r#"{"@m":"hello"}"#, "missing a `@t` timestamp"),
+            (
+                r#"{"@t":"2020-02-13 00:51:39"}"#,
+                "`@t` should be an ISO 8601 timestamp with an offset, but `2020-02-13 00:51:39` is invalid",
+            ),
+            (r#"{"@t":1581555099}"#, "`@t` should be a string, but is a number"),
+            (r#"{"@t":"2020-02-13T00:51:39Z","@l":3}"#, "`@l` should be a string, but is a number"),
+            (r#"{"@t":"2020-02-13T00:51:39Z","@r":["1"]}"#, "`@r` renderings need a `@mt` message template"),
+            (
+                r#"{"@t":"2020-02-13T00:51:39Z","@host":"web-1"}"#,
+                "`@host` isn't a reified property, user properties starting with `@` need to be escaped as `@@host`",
+            ),
+            (
+                r#"{"@t":"2020-02-13T00:51:39Z","host":"web-1","host":"web-2"}"#,
+                "`host` appears more than once",
+            ),
+            (r#"{"@t":"2020-02-13T00:51:39Z","@i":-1}"#, "`@i` should be a hex string or 32-bit unsigned number"),
+        ];
+
+        for (event, expected) in cases {
+            let problems = validate(event);
+
+            assert_eq!(1, problems.len(), "{:?}", problems);
+            assert!(problems[0].starts_with(expected), "{:?}", problems);
+        }
+    }
+}
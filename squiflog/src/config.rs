@@ -1,12 +1,13 @@
 use std::{env, str::FromStr};
 
-use crate::{data, diagnostics, error::Error, server};
+use crate::{data, diagnostics, error::Error, server, stats};
 
 #[derive(Debug, Default, Clone)]
 pub struct Config {
     pub data: data::Config,
     pub server: server::Config,
     pub diagnostics: diagnostics::Config,
+    pub stats: stats::Config,
 }
 
 impl Config {
@@ -30,6 +31,67 @@ impl Config {
             config.diagnostics.min_level = diagnostics::Level::Debug;
         }
 
+        let stats_address_var = if is_seq_app {
+            "SEQ_APP_SETTING_STATSADDRESS"
+        } else {
+            "SYSLOG_STATS_ADDRESS"
+        };
+        let mut stats_address = String::new();
+        read_environment(&mut stats_address, stats_address_var)?;
+        if !stats_address.is_empty() {
+            config.stats.addr = Some(stats_address);
+        }
+
+        let strictness_var = if is_seq_app {
+            "SEQ_APP_SETTING_STRICTNESS"
+        } else {
+            "SYSLOG_STRICTNESS"
+        };
+        read_environment(&mut config.data.strictness, strictness_var)?;
+
+        let precedence_var = if is_seq_app {
+            "SEQ_APP_SETTING_PRECEDENCE"
+        } else {
+            "SYSLOG_PRECEDENCE"
+        };
+        read_environment(&mut config.data.precedence, precedence_var)?;
+
+        let properties_var = if is_seq_app {
+            "SEQ_APP_SETTING_PROPERTIES"
+        } else {
+            "SYSLOG_PROPERTIES"
+        };
+        let mut properties = Pairs::default();
+        read_environment(&mut properties, properties_var)?;
+        config
+            .data
+            .properties
+            .extend(properties.0.into_iter().map(|(name, value)| (name, value.into())));
+
+        let rename_var = if is_seq_app {
+            "SEQ_APP_SETTING_RENAMEPROPERTIES"
+        } else {
+            "SYSLOG_RENAME_PROPERTIES"
+        };
+        let mut renames = Pairs::default();
+        read_environment(&mut renames, rename_var)?;
+        config
+            .data
+            .rules
+            .extend(renames.0.into_iter().map(|(from, to)| data::Rule::Rename { from, to }));
+
+        let remove_var = if is_seq_app {
+            "SEQ_APP_SETTING_REMOVEPROPERTIES"
+        } else {
+            "SYSLOG_REMOVE_PROPERTIES"
+        };
+        let mut removals = List::default();
+        read_environment(&mut removals, remove_var)?;
+        config
+            .data
+            .rules
+            .extend(removals.0.into_iter().map(|name| data::Rule::Remove { name }));
+
         Ok(config)
     }
 }
@@ -41,10 +103,10 @@ pub fn is_seq_app() -> bool {
 fn is_truthy(name: impl AsRef<str>) -> Result<bool, Error> {
     match env::var(name.as_ref()) {
         // The evironment variable contains a truthy value
-        Ok(ref v) if v == "True" || v == "true" => return Ok(true),
+        Ok(ref v) if v == "True" || v == "true" => Ok(true),
         // The environment variable is not set or doesn't contain
         // a truthy value
-        Ok(_) | Err(env::VarError::NotPresent) => return Ok(false),
+        Ok(_) | Err(env::VarError::NotPresent) => Ok(false),
         // The environment variable is invalid
         Err(e) => Err(e)?,
     }
@@ -57,9 +119,9 @@ where
 {
     match env::var(name.as_ref()) {
         // The environment variable exists, but is empty
-        Ok(ref v) if v == "" => return Ok(()),
+        Ok(ref v) if v.is_empty() => Ok(()),
         // The environment variable does not exist
-        Err(env::VarError::NotPresent) => return Ok(()),
+        Err(env::VarError::NotPresent) => Ok(()),
         // The environment variable is invalid
         Err(e) => Err(e)?,
         // The environment variable has a value
@@ -70,3 +132,70 @@ where
         }
     }
 }
+
+/**
+A comma-separated list, like `facility,proc_id`.
+*/
+#[derive(Debug, Default, PartialEq)]
+struct List(Vec<String>);
+
+impl FromStr for List {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(List(
+            s.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        ))
+    }
+}
+
+/**
+A comma-separated list of pairs, like `environment=production,region=us`.
+*/
+#[derive(Debug, Default, PartialEq)]
+struct Pairs(Vec<(String, String)>);
+
+impl FromStr for Pairs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let List(items) = s.parse()?;
+
+        items
+            .into_iter()
+            .map(|item| match item.split_once('=') {
+                Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_owned(), value.trim().to_owned())),
+                _ => Err(Error::msg(format!("invalid pair `{}`, expected `name=value`", item))),
+            })
+            .collect::<Result<_, _>>()
+            .map(Pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_are_parsed() {
+        assert_eq!(
+            List(vec!["facility".to_owned(), "proc_id".to_owned()]),
+            " facility, ,proc_id ".parse().unwrap()
+        );
+
+        assert_eq!(
+            Pairs(vec![
+                ("environment".to_owned(), "production".to_owned()),
+                ("hostname".to_owned(), "host".to_owned()),
+            ]),
+            "environment=production, hostname = host".parse().unwrap()
+        );
+
+        "environment".parse::<Pairs>().expect_err("should fail");
+        "=production".parse::<Pairs>().expect_err("should fail");
+    }
+}
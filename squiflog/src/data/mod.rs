@@ -1,21 +1,36 @@
 use std::{
-    collections::HashMap,
-    io,
-    str,
-    io::Write
+    fmt,
+    str::FromStr,
+    sync::Arc,
 };
 
-use serde_json::{
-    self,
-    json,
+use chrono::{
+    DateTime,
+    Utc,
 };
 
-use crate::error::Error;
-use chrono::Utc;
+use serde_json::Value;
 
-mod clef;
-mod parsers;
-pub mod syslog;
+pub use squiflog_core::{
+    clef::{
+        self,
+        Precedence,
+    },
+    enrich::{
+        self,
+        Enricher,
+    },
+    output::{
+        self,
+        Output,
+    },
+    syslog,
+};
+
+use crate::{
+    error::Error,
+    stats,
+};
 
 metrics! {
     msg
@@ -24,12 +39,163 @@ metrics! {
 /**
 Configuration for CLEF formatting.
 */
-#[derive(Debug, Clone)]
-pub struct Config {}
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /**
+    Which SYSLOG formats to accept.
+    */
+    pub strictness: Strictness,
+    /**
+    Which fields to keep when the SYSLOG header conflicts with structured data.
+    */
+    pub precedence: Precedence,
+    /**
+    Additional properties to attach to every event.
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {}
+    These properties never replace ones from the SYSLOG message itself.
+    They're attached before any other enrichers run.
+    */
+    pub properties: Vec<(String, Value)>,
+    /**
+    Rules for renaming and removing properties.
+
+    Rules run in order after properties are attached, and before any other enrichers.
+    */
+    pub rules: Vec<Rule>,
+}
+
+impl Config {
+    /**
+    Parse a SYSLOG message in one of the accepted formats.
+    */
+    pub fn parse<'a>(&self, msg: &'a [u8], received: &DateTime<Utc>) -> Result<(syslog::Message<'a>, Format), Error> {
+        let (syslog, format, _) = self.parse_with_fallback(msg, received)?;
+
+        Ok((syslog, format))
+    }
+
+    // Also returns why the message isn't RFC 5424 if it was parsed as RFC 3164 instead
+    fn parse_with_fallback<'a>(
+        &self,
+        msg: &'a [u8],
+        received: &DateTime<Utc>,
+    ) -> Result<(syslog::Message<'a>, Format, Option<Error>), Error> {
+        match (syslog::Message::from_rfc5424_bytes(msg), self.strictness) {
+            (Ok(syslog), _) => Ok((syslog, Format::Rfc5424, None)),
+            (Err(err), Strictness::Lenient) => Ok((
+                syslog::Message::from_rfc3164_bytes(msg, received),
+                Format::Rfc3164,
+                Some(err),
+            )),
+            (Err(err), Strictness::Rfc5424) => Err(err),
+        }
+    }
+
+    /**
+    All enrichment rules in the order they run, starting with properties.
+    */
+    pub fn enrichment_rules(&self) -> Vec<Rule> {
+        self.properties
+            .iter()
+            .map(|(name, value)| Rule::Property {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .chain(self.rules.iter().cloned())
+            .collect()
+    }
+}
+
+/**
+A configured enrichment rule.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /**
+    Attach a property to every event.
+    */
+    Property { name: String, value: Value },
+    /**
+    Rename a property on every event that has it.
+    */
+    Rename { from: String, to: String },
+    /**
+    Remove a property from every event that has it.
+    */
+    Remove { name: String },
+}
+
+impl Rule {
+    /**
+    Get an enricher that applies the rule.
+    */
+    pub fn enricher(&self) -> Box<dyn Enricher> {
+        match self {
+            Rule::Property { name, value } => Box::new(enrich::property(&**name, value.clone())),
+            Rule::Rename { from, to } => Box::new(enrich::rename(&**from, &**to)),
+            Rule::Remove { name } => Box::new(enrich::remove(&**name)),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Property { name, value } => write!(f, "property `{}` = {}", name, value),
+            Rule::Rename { from, to } => write!(f, "rename `{}` to `{}`", from, to),
+            Rule::Remove { name } => write!(f, "remove `{}`", name),
+        }
+    }
+}
+
+/**
+The format a SYSLOG message was parsed as.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Rfc5424,
+    Rfc3164,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Rfc5424 => f.write_str("RFC 5424"),
+            Format::Rfc3164 => f.write_str("RFC 3164"),
+        }
+    }
+}
+
+/**
+Which SYSLOG formats are accepted.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /**
+    Accept RFC 5424 messages, falling back to RFC 3164 for anything else.
+
+    Any input is accepted, even if it's not a SYSLOG message.
+    */
+    #[default]
+    Lenient,
+    /**
+    Only accept RFC 5424 messages.
+    */
+    Rfc5424,
+}
+
+impl FromStr for Strictness {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "lenient" => Ok(Strictness::Lenient),
+            "rfc5424" => Ok(Strictness::Rfc5424),
+            _ => Err(Error::msg(format!(
+                "invalid strictness `{}`, expected `Lenient` or `Rfc5424`",
+                s
+            ))),
+        }
     }
 }
 
@@ -40,227 +206,376 @@ pub fn build(config: Config) -> Data {
     Data::new(config)
 }
 
-#[derive(Clone)]
-pub struct Data {}
+/**
+A builder for a CLEF processor.
 
-impl Data {
-    pub fn new(_: Config) -> Self {
-        Data {}
+Events are written to stdout unless another output is given.
+*/
+#[derive(Default)]
+pub struct Builder {
+    config: Config,
+    enrichers: Vec<Box<dyn Enricher>>,
+    output: Option<Box<dyn Output>>,
+}
+
+impl Builder {
+    /**
+    Start from the default configuration, writing events to stdout.
+    */
+    pub fn new() -> Self {
+        Builder::default()
     }
 
-    pub fn read_as_clef(&self, msg: &[u8]) -> Result<(), Error> {
-        increment!(data.msg);
-        let syslog = syslog::Message::from_bytes(msg);
-        let clef = syslog.into_clef();
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
+    /**
+    Start from an existing configuration.
+    */
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
 
-        serde_json::to_writer(&mut stdout, &clef)?;
-        stdout.write_all(b"\n")?;
+    /**
+    Set which SYSLOG formats are accepted.
+    */
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.config.strictness = strictness;
+        self
+    }
 
-        Ok(())
+    /**
+    Set which fields are kept when the SYSLOG header and structured data conflict.
+    */
+    pub fn precedence(mut self, precedence: Precedence) -> Self {
+        self.config.precedence = precedence;
+        self
     }
-}
 
-impl<'a> syslog::Message<'a> {
     /**
-    Covert a SYSLOG message into CLEF.
+    Attach a property to every event.
+    */
+    pub fn property(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.config.properties.push((name.into(), value.into()));
+        self
+    }
 
-    The contents of the SYSLOG message is inspected and deserialized as CLEF-encoded
-    JSON if possible. In this case, timestamp, message, and level information from
-    the embedded CLEF is given precedence over the SYSLOG header.
+    /**
+    Run an enricher over every event before it's emitted.
 
-    Other fields with conflicting names are prioritized:
+    Enrichers run in the order they're added.
+    */
+    pub fn enrich(mut self, enricher: impl Enricher) -> Self {
+        self.enrichers.push(Box::new(enricher));
+        self
+    }
 
-      SYSLOG header > SYSLOG structured data > SYSLOG message embedded CLEF/JSON
+    /**
+    Emit CLEF events to the given output instead of stdout.
+    */
+    pub fn output(mut self, output: impl Output) -> Self {
+        self.output = Some(Box::new(output));
+        self
+    }
 
-    This means fields set by the system/on the logger are preferred over
-    the fields attached to any one event.
+    /**
+    Build a CLEF processor.
 
-    If fields conflict, then the lower-priority field is included with a
-    double-underscore-prefixed name, e.g.: "__host".
+    Configured rules run before any enrichers added with `enrich`.
     */
-    pub fn into_clef(self) -> clef::Message<'a> {
-        #![deny(unused_variables)]
-
-        let syslog::Message {
-            priority,
-            timestamp,
-            hostname,
-            app_name,
-            proc_id,
-            message_id,
-            structured_data,
-            message,
-        } = self;
-
-        let mut additional = HashMap::new();
-
-        additional.insert("facility", json!(priority.facility()));
-        if let Some(hostname) = hostname {
-            additional.insert("hostname", json!(hostname));
-        }
-        if let Some(app_name) = app_name {
-            additional.insert("app_name", json!(app_name));
-        }
-        if let Some(proc_id) = proc_id {
-            additional.insert("proc_id", json!(proc_id));
-        }
-        if let Some(message_id) = message_id {
-            additional.insert("message_id", json!(message_id));
-        }
+    pub fn build(self) -> Data {
+        let output: Arc<dyn Output> = match self.output {
+            Some(output) => Arc::from(output),
+            None => Arc::new(output::stdout()),
+        };
 
-        if let Some(sd) = structured_data {
-            for element in sd {
-                let mut params = vec![];
-                for (k, v) in element.params {
-                    let mut map = HashMap::new();
-                    map.insert(k, v);
-                    params.push(map);
-                }
-                additional.insert(element.id, json!(params));
-            }
+        let enrichers = self
+            .config
+            .enrichment_rules()
+            .into_iter()
+            .map(|rule| Stage {
+                enricher: rule.enricher(),
+                rule: Some(rule),
+            })
+            .chain(self.enrichers.into_iter().map(|enricher| Stage { rule: None, enricher }))
+            .collect::<Vec<_>>();
+
+        Data {
+            config: Arc::new(self.config),
+            enrichers: Arc::from(enrichers),
+            output,
         }
+    }
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("config", &self.config)
+            .field("enrichers", &self.enrichers.len())
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct Data {
+    config: Arc<Config>,
+    enrichers: Arc<[Stage]>,
+    output: Arc<dyn Output>,
+}
+
+struct Stage {
+    // The configured rule the enricher came from, if any
+    rule: Option<Rule>,
+    enricher: Box<dyn Enricher>,
+}
+
+/**
+A step taken by a message being processed.
+*/
+#[derive(Debug)]
+pub enum Step<'a> {
+    /**
+    The message was parsed and converted into CLEF.
+
+    Messages parsed as RFC 3164 have the reason they aren't RFC 5424 in `fallback`.
+    */
+    Parsed {
+        format: Format,
+        fallback: Option<&'a Error>,
+        evt: &'a clef::Message<'a>,
+    },
+    /**
+    An enricher ran over the event.
+
+    Enrichers added to the builder directly don't have a rule.
+    */
+    Enriched { rule: Option<&'a Rule>, evt: &'a clef::Message<'a> },
+}
+
+impl Data {
+    pub fn new(config: Config) -> Self {
+        Builder::new().config(config).build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn read_as_clef(&self, msg: &[u8]) -> Result<Format, Error> {
+        self.read_as_clef_at(msg, Utc::now())
+    }
+
+    /**
+    Process a message that was received at the given time.
+
+    RFC 3164 timestamps don't include a year, so it's inferred from when the
+    message was received. That's not now for messages read from archives.
+
+    The format the message was parsed as is returned.
+    */
+    pub fn read_as_clef_at(&self, msg: &[u8], received: DateTime<Utc>) -> Result<Format, Error> {
+        self.trace_as_clef_at(msg, received, |_| ())
+    }
 
-        clef::Message {
-            timestamp: timestamp.unwrap_or_else(|| Utc::now()),
-            level: Some(priority.severity()),
-            message,
-            message_template: None,
-            exception: None,
-            additional,
+    /**
+    Process a message, calling `trace` after each step it takes.
+
+    This is the same processing as [`Data::read_as_clef_at`], so it can be used to
+    explain what happens to a message.
+    */
+    pub fn trace_as_clef_at(
+        &self,
+        msg: &[u8],
+        received: DateTime<Utc>,
+        mut trace: impl FnMut(Step),
+    ) -> Result<Format, Error> {
+        increment!(data.msg);
+        let (syslog, format, fallback) = self.config.parse_with_fallback(msg, &received)?;
+
+        let mut clef = syslog.into_clef_with(self.config.precedence);
+        trace(Step::Parsed {
+            format,
+            fallback: fallback.as_ref(),
+            evt: &clef,
+        });
+
+        for stage in &*self.enrichers {
+            stage.enricher.enrich(&mut clef);
+            trace(Step::Enriched {
+                rule: stage.rule.as_ref(),
+                evt: &clef,
+            });
         }
+
+        stats::event(format, &clef);
+        self.output.emit(&clef)?;
+
+        Ok(format)
     }
 }
 
 #[cfg(test)]
-mod test {
+mod tests {
     use super::*;
-    use serde_json::{
-        self,
-        json,
+    use serde_json::json;
+    use std::{
+        io::{
+            self,
+            Write,
+        },
+        sync::Mutex,
     };
-    use std::borrow::Cow::Borrowed;
-    use crate::test_util::to_timestamp;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn events(&self) -> Vec<Value> {
+            let buf = self.0.lock().unwrap();
+            serde_json::Deserializer::from_slice(&buf)
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        }
+    }
 
     #[test]
-    fn syslog_to_clef() {
+    fn builder_writes_to_output() {
+        let output = Buffer::default();
+
+        let data = Data::builder()
+            .property("environment", "test")
+            .property("hostname", "configured")
+            .output(output::Writer::new(output.clone()))
+            .build();
+
+        data.read_as_clef(b"<30>1 2020-02-13T00:51:39.527825Z docker-desktop - - - - hello world")
+            .expect("failed to process message");
+
         let expected = json!({
             "@l": "info",
             "@m": "hello world",
             "@t": "2020-02-13T00:51:39.527825Z",
             "facility": "daemon",
             "hostname": "docker-desktop",
-            "app_name": "8b1089798cf8",
-            "proc_id": "1481",
-            "message_id": "8b1089798cf8",
+            "__hostname": "configured",
+            "environment": "test",
         });
 
-        let message = "hello world";
-
-        let syslog = syslog::Message {
-            priority: syslog::Priority {
-                facility: 3,
-                severity: 6,
-            },
-            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
-            hostname: Some("docker-desktop"),
-            app_name: Some("8b1089798cf8"),
-            proc_id: Some("1481"),
-            message_id: Some("8b1089798cf8"),
-            structured_data: None,
-            message: Some(Borrowed(message)),
-        };
-
-        let clef = syslog.into_clef();
-        let actual = serde_json::to_value(clef).unwrap();
-
-        assert_eq!(expected, actual);
+        assert_eq!(vec![expected], output.events());
     }
 
     #[test]
-    fn syslog_to_clef_with_structured_data() {
+    fn builder_runs_enrichers_after_properties() {
+        let output = Buffer::default();
+
+        let data = Data::builder()
+            .property("environment", "test")
+            .enrich(enrich::rename("environment", "env"))
+            .enrich(enrich::remove("facility"))
+            .output(output::Writer::new(output.clone()))
+            .build();
+
+        data.read_as_clef(b"<30>1 2020-02-13T00:51:39.527825Z docker-desktop - - - - hello world")
+            .expect("failed to process message");
+
         let expected = json!({
             "@l": "info",
             "@m": "hello world",
             "@t": "2020-02-13T00:51:39.527825Z",
-            "facility": "daemon",
             "hostname": "docker-desktop",
-            "app_name": "8b1089798cf8",
-            "proc_id": "1481",
-            "message_id": "8b1089798cf8",
-            "sdid1234": [{ "hello": "world" }, { "event": "value" }]
+            "env": "test",
         });
 
-        let message = "hello world";
-
-        let mut sd_params = vec![];
-        sd_params.push(("hello", "world".to_owned()));
-        sd_params.push(("event", "value".to_owned()));
-
-        let syslog = syslog::Message {
-            priority: syslog::Priority {
-                facility: 3,
-                severity: 6,
-            },
-            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
-            hostname: Some("docker-desktop"),
-            app_name: Some("8b1089798cf8"),
-            proc_id: Some("1481"),
-            message_id: Some("8b1089798cf8"),
-            structured_data: Some(vec![syslog::StructuredDataElement {
-                id: "sdid1234",
-                params: sd_params,
-            }]),
-            message: Some(Borrowed(message)),
+        assert_eq!(vec![expected], output.events());
+    }
+
+    #[test]
+    fn builder_runs_configured_rules_before_enrichers() {
+        let output = Buffer::default();
+
+        let config = Config {
+            rules: vec![
+                Rule::Rename {
+                    from: "hostname".to_owned(),
+                    to: "host".to_owned(),
+                },
+                Rule::Remove {
+                    name: "facility".to_owned(),
+                },
+            ],
+            ..Config::default()
         };
 
-        let clef = syslog.into_clef();
-        let actual = serde_json::to_value(clef).unwrap();
+        let data = Data::builder()
+            .config(config)
+            .property("environment", "test")
+            .enrich(enrich::rename("host", "machine"))
+            .output(output::Writer::new(output.clone()))
+            .build();
 
-        assert_eq!(expected, actual);
-    }
+        data.read_as_clef(b"<30>1 2020-02-13T00:51:39.527825Z docker-desktop - - - - hello world")
+            .expect("failed to process message");
 
-    #[test]
-    fn syslog_to_clef_with_structured_data_with_duplicated_params() {
         let expected = json!({
             "@l": "info",
             "@m": "hello world",
             "@t": "2020-02-13T00:51:39.527825Z",
-            "facility": "daemon",
-            "hostname": "docker-desktop",
-            "app_name": "8b1089798cf8",
-            "proc_id": "1481",
-            "message_id": "8b1089798cf8",
-            "sdid1234": [{ "ip": "192.0.2.1" }, { "ip": "192.0.2.129" }]
+            "machine": "docker-desktop",
+            "environment": "test",
         });
 
-        let message = "hello world";
-
-        let mut sd_params = vec![];
-        sd_params.push(("ip", "192.0.2.1".to_owned()));
-        sd_params.push(("ip", "192.0.2.129".to_owned()));
-
-        let syslog = syslog::Message {
-            priority: syslog::Priority {
-                facility: 3,
-                severity: 6,
-            },
-            timestamp: to_timestamp("2020-02-13T00:51:39.527825Z"),
-            hostname: Some("docker-desktop"),
-            app_name: Some("8b1089798cf8"),
-            proc_id: Some("1481"),
-            message_id: Some("8b1089798cf8"),
-            structured_data: Some(vec![syslog::StructuredDataElement {
-                id: "sdid1234",
-                params: sd_params,
-            }]),
-            message: Some(Borrowed(message)),
-        };
+        assert_eq!(vec![expected], output.events());
+    }
+
+    #[test]
+    fn trace_reports_each_step() {
+        let output = Buffer::default();
+
+        let data = Data::builder()
+            .property("environment", "test")
+            .enrich(enrich::remove("facility"))
+            .output(output::Writer::new(output.clone()))
+            .build();
+
+        let mut steps = Vec::new();
+        let format = data
+            .trace_as_clef_at(b"<34>Oct 11 22:14:15 mymachine su: failed", Utc::now(), |step| {
+                steps.push(match step {
+                    Step::Parsed { format, fallback, evt } => {
+                        format!("{} {} {}", format, fallback.is_some(), evt.additional.len())
+                    }
+                    Step::Enriched { rule, evt } => format!("{} {}", rule.is_some(), evt.additional.len()),
+                })
+            })
+            .expect("failed to process message");
+
+        assert_eq!(Format::Rfc3164, format);
+        assert_eq!(vec!["RFC 3164 true 2", "true 3", "false 2"], steps);
+        assert_eq!(1, output.events().len());
+    }
+
+    #[test]
+    fn strict_builder_rejects_rfc3164() {
+        let output = Buffer::default();
+
+        let data = Data::builder()
+            .strictness(Strictness::Rfc5424)
+            .output(output::Writer::new(output.clone()))
+            .build();
 
-        let clef = syslog.into_clef();
-        let actual = serde_json::to_value(clef).unwrap();
+        data.read_as_clef(b"<34>Oct 11 22:14:15 mymachine su: 'su root' failed")
+            .expect_err("should fail");
 
-        assert_eq!(expected, actual);
+        assert_eq!(0, output.events().len());
     }
 }
@@ -16,6 +16,4 @@ pub mod error;
 pub mod config;
 pub mod data;
 pub mod server;
-
-#[cfg(test)]
-mod test_util;
+pub mod stats;
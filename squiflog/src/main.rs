@@ -1,5 +1,7 @@
 extern crate squiflog;
 
+mod cli;
+
 use bytes::Bytes;
 use std::{any::Any, io::Read, panic::catch_unwind, thread};
 
@@ -9,6 +11,7 @@ use squiflog::{
     diagnostics::{self, emit, emit_err},
     error::Error,
     server,
+    stats,
 };
 
 fn run() -> Result<(), Box<dyn std::error::Error>> {
@@ -17,10 +20,13 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize diagnostics
     diagnostics::init(config.diagnostics);
 
+    // Serve stats for `squiflog dashboard`, if they're enabled
+    stats::init(config.stats, format!("udp://{}", config.server.bind.addr))?;
+
     // The processor for converting SYSLOG into CLEF
     let process = {
         let data = data::build(config.data);
-        move |msg: Bytes| data.read_as_clef(&*msg)
+        move |msg: Bytes| data.read_as_clef(&msg).map(|_| ())
     };
 
     // The server that drives the receiver and processor
@@ -64,7 +70,22 @@ fn listen_for_stdin_closed(handle: server::Handle) {
 }
 
 fn main() {
-    let run_server: Result<(), Box<dyn std::error::Error>> = catch_unwind(|| run())
+    let cli = cli::parse();
+
+    match cli.command {
+        // Without a subcommand we run the server, which is how the Seq app starts it
+        None => serve(),
+        Some(command) => {
+            if let Err(err) = cli::run(command) {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn serve() {
+    let run_server: Result<(), Box<dyn std::error::Error>> = catch_unwind(run)
         .map_err(|panic| unwrap_panic(panic).into())
         .and_then(|inner| inner);
 
@@ -8,6 +8,14 @@ use bytes::Bytes;
 
 use crate::diagnostics::*;
 use crate::error::Error;
+use crate::stats;
+
+pub use squiflog_core::input::{
+    Incoming,
+    Input,
+};
+
+pub use self::udp::UdpInput;
 
 mod udp;
 
@@ -110,20 +118,36 @@ impl Handle {
     }
 }
 
+/**
+Build a server that receives messages from the configured address.
+*/
 pub fn build(
     config: Config,
+    process: impl FnMut(Bytes) -> Result<(), Error> + Send + Sync + Unpin + Clone + 'static,
+) -> Result<Server, Error> {
+    let input = match config.bind.protocol {
+        Protocol::Udp => UdpInput::new(config.bind.addr.parse()?),
+    };
+
+    build_with(input, process)
+}
+
+/**
+Build a server that receives messages from a custom input.
+*/
+pub fn build_with(
+    input: impl Input,
     mut process: impl FnMut(Bytes) -> Result<(), Error> + Send + Sync + Unpin + Clone + 'static,
 ) -> Result<Server, Error> {
     emit("Starting SYSLOG server");
 
-    let addr = config.bind.addr.parse()?;
     let (handle_tx, handle_rx) = oneshot::channel();
 
     // Build a handle
     let handle = Some(Handle { close: handle_tx });
 
     let server = async move {
-        let incoming = udp::Server::bind(&addr).await?.build();
+        let incoming = input.bind().await?;
 
         let mut close = handle_rx.fuse();
         let mut ctrl_c = ctrl_c().boxed().fuse();
@@ -138,14 +162,17 @@ pub fn build(
                     // A complete message has been received
                     Some(Ok(msg)) => {
                         increment!(server.receive_ok);
+                        stats::receive();
 
                         // Process the received message
                         match process(msg) {
                             Ok(()) => {
                                 increment!(server.process_ok);
+                                stats::process();
                             }
                             Err(err) => {
                                 increment!(server.process_err);
+                                stats::dropped(stats::reason(&err));
                                 emit_err(&err, "SYSLOG processing failed");
                             }
                         }
@@ -153,10 +180,14 @@ pub fn build(
                     // An error occurred receiving a chunk
                     Some(Err(err)) => {
                         increment!(server.receive_err);
+                        stats::receive();
+                        stats::dropped(stats::reason(&err));
                         emit_err(&err, "SYSLOG processing failed");
                     },
+                    // The input has no more messages
                     None => {
-                        unreachable!("receiver stream should never terminate")
+                        emit("Input closed; shutting down");
+                        break;
                     },
                 },
                 // A termination signal from the programmatic handle
@@ -186,3 +217,48 @@ pub fn build(
         handle,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+
+    struct Messages(Vec<&'static [u8]>);
+
+    impl Input for Messages {
+        fn bind(self) -> BoxFuture<'static, Result<Incoming, Error>> {
+            let incoming = stream::iter(self.0.into_iter().map(|msg| Ok(Bytes::from_static(msg))));
+
+            async move { Ok(incoming.boxed()) }.boxed()
+        }
+    }
+
+    #[test]
+    fn server_processes_messages_from_custom_input() {
+        let received = Arc::new(Mutex::new(vec![]));
+
+        let server = build_with(Messages(vec![b"<30>1 - - - - - - hello", b"<30>1 - - - - - - world"]), {
+            let received = received.clone();
+            move |msg| {
+                received.lock().unwrap().push(msg);
+                Ok(())
+            }
+        })
+        .expect("failed to build server");
+
+        // The server stops when the input has no more messages
+        server.run().expect("failed to run server");
+
+        assert_eq!(
+            vec![
+                Bytes::from_static(b"<30>1 - - - - - - hello"),
+                Bytes::from_static(b"<30>1 - - - - - - world"),
+            ],
+            *received.lock().unwrap()
+        );
+    }
+}
@@ -4,25 +4,39 @@ use crate::{diagnostics::*, error::Error};
 
 use bytes::{Bytes, BytesMut};
 
-use futures::{Stream, StreamExt};
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+
+use squiflog_core::input::{Incoming, Input};
 
 use tokio::net::UdpSocket;
 
 use tokio_util::{codec::Decoder, udp::UdpFramed};
 
-pub(super) struct Server(UdpSocket);
-
-impl Server {
-    pub(super) async fn bind(addr: &SocketAddr) -> Result<Self, Error> {
-        let sock = UdpSocket::bind(&addr).await?;
+/**
+An input that receives SYSLOG messages as UDP datagrams.
+*/
+pub struct UdpInput {
+    addr: SocketAddr,
+}
 
-        Ok(Server(sock))
+impl UdpInput {
+    pub fn new(addr: SocketAddr) -> Self {
+        UdpInput { addr }
     }
+}
 
-    pub(super) fn build(self) -> impl Stream<Item = Result<Bytes, Error>> {
-        emit("Setting up for UDP");
+impl Input for UdpInput {
+    fn bind(self) -> BoxFuture<'static, Result<Incoming, Error>> {
+        async move {
+            let sock = UdpSocket::bind(&self.addr).await?;
 
-        UdpFramed::new(self.0, Decode).map(|r| r.map(|(msg, _)| msg)) // ignore socket, just take message
+            emit("Setting up for UDP");
+
+            let incoming = UdpFramed::new(sock, Decode).map(|r| r.map(|(msg, _)| msg)); // ignore socket, just take message
+
+            Ok(incoming.boxed())
+        }
+        .boxed()
     }
 }
 
@@ -0,0 +1,428 @@
+/*!
+Live statistics about the messages a running server has received.
+
+Statistics are only collected when an address to serve them on is configured.
+`GET /stats` on that address returns a `Snapshot` as JSON, which is what
+`squiflog dashboard` shows.
+*/
+
+use std::{
+    collections::BTreeMap,
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        Read,
+        Write,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    sync::{
+        atomic::{
+            AtomicBool,
+            AtomicU64,
+            AtomicUsize,
+            Ordering,
+        },
+        Mutex,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use squiflog_core::clef;
+
+use crate::{
+    data::Format,
+    diagnostics::*,
+    error::Error,
+};
+
+// The most distinct names counted for hostnames, apps, and drop reasons
+// Any others are counted together so a noisy network can't exhaust memory
+const MAX_NAMES: usize = 1000;
+const OTHER: &str = "(other)";
+
+// Requests are tiny, so anything slow or large isn't a stats client
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_SIZE: u64 = 8 * 1024;
+
+// Each connection is served on its own thread, so a slow client can't hold up others
+// Connections beyond this are closed straight away
+const MAX_CONNECTIONS: usize = 16;
+
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref STATS: Stats = Stats::new();
+}
+
+/**
+Statistics configuration.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /**
+    The address to serve statistics on, like `127.0.0.1:5140`.
+
+    Statistics aren't collected unless this is set.
+    */
+    pub addr: Option<String>,
+}
+
+/**
+Start serving statistics for the server receiving messages on `listener`.
+*/
+pub fn init(config: Config, listener: impl Into<String>) -> Result<(), Error> {
+    let addr = match config.addr {
+        Some(addr) => addr,
+        None => return Ok(()),
+    };
+
+    let server = TcpListener::bind(&addr)
+        .map_err(|err| Error::msg(format!("failed to serve stats on `{}`: {}", addr, err)))?;
+
+    *STATS.listener.lock().expect("failed to lock stats") = listener.into();
+    ENABLED.store(true, Ordering::Relaxed);
+
+    emit("Serving SYSLOG stats");
+
+    // NOTE: Like diagnostics, stats are served from regular threads
+    // instead of `tokio` so they're independent of the server
+    thread::spawn(move || serve(server, &STATS));
+
+    Ok(())
+}
+
+/**
+A point-in-time copy of a server's statistics.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /**
+    The address the server receives messages on, like `udp://0.0.0.0:514`.
+    */
+    pub listener: String,
+    pub uptime_secs: u64,
+    /**
+    Messages received, including any that couldn't be received completely.
+    */
+    pub received: u64,
+    pub processed: u64,
+    pub dropped: u64,
+    pub formats: BTreeMap<String, u64>,
+    pub hostnames: BTreeMap<String, u64>,
+    pub apps: BTreeMap<String, u64>,
+    pub drop_reasons: BTreeMap<String, u64>,
+}
+
+pub(crate) fn receive() {
+    if let Some(stats) = enabled() {
+        stats.received.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn process() {
+    if let Some(stats) = enabled() {
+        stats.processed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn dropped(reason: impl Into<String>) {
+    if let Some(stats) = enabled() {
+        stats.dropped(reason.into());
+    }
+}
+
+pub(crate) fn event(format: Format, evt: &clef::Message) {
+    if let Some(stats) = enabled() {
+        stats.event(format, evt);
+    }
+}
+
+/**
+A reason a message was dropped, without the byte offset so similar failures are grouped.
+*/
+pub(crate) fn reason(err: &Error) -> String {
+    match err {
+        Error::Framing { msg, .. } => format!("invalid framing: {}", msg),
+        Error::Header { msg, .. } => format!("invalid header: {}", msg),
+        Error::StructuredData { msg, .. } => format!("invalid structured data: {}", msg),
+        Error::Encoding { msg, .. } => format!("invalid encoding: {}", msg),
+        err => err.to_string(),
+    }
+}
+
+fn enabled() -> Option<&'static Stats> {
+    if ENABLED.load(Ordering::Relaxed) {
+        Some(&STATS)
+    } else {
+        None
+    }
+}
+
+struct Stats {
+    started: Instant,
+    listener: Mutex<String>,
+    received: AtomicU64,
+    processed: AtomicU64,
+    names: Mutex<Names>,
+}
+
+#[derive(Default)]
+struct Names {
+    dropped: u64,
+    formats: Counts,
+    hostnames: Counts,
+    apps: Counts,
+    drop_reasons: Counts,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Stats {
+            started: Instant::now(),
+            listener: Mutex::new(String::new()),
+            received: AtomicU64::new(0),
+            processed: AtomicU64::new(0),
+            names: Mutex::new(Names::default()),
+        }
+    }
+
+    fn dropped(&self, reason: String) {
+        let mut names = self.names.lock().expect("failed to lock stats");
+
+        names.dropped += 1;
+        names.drop_reasons.add(reason);
+    }
+
+    fn event(&self, format: Format, evt: &clef::Message) {
+        let name = |property| {
+            evt.additional
+                .get(property)
+                .and_then(|value| value.as_str())
+                .unwrap_or("-")
+        };
+
+        let mut names = self.names.lock().expect("failed to lock stats");
+
+        names.formats.add(format.to_string());
+        names.hostnames.add(name("hostname"));
+        names.apps.add(name("app_name"));
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let names = self.names.lock().expect("failed to lock stats");
+
+        Snapshot {
+            listener: self.listener.lock().expect("failed to lock stats").clone(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            received: self.received.load(Ordering::Relaxed),
+            processed: self.processed.load(Ordering::Relaxed),
+            dropped: names.dropped,
+            formats: names.formats.0.clone(),
+            hostnames: names.hostnames.0.clone(),
+            apps: names.apps.0.clone(),
+            drop_reasons: names.drop_reasons.0.clone(),
+        }
+    }
+}
+
+/**
+Counts of names, up to `MAX_NAMES`.
+*/
+#[derive(Default)]
+struct Counts(BTreeMap<String, u64>);
+
+impl Counts {
+    fn add(&mut self, name: impl AsRef<str> + Into<String>) {
+        if let Some(count) = self.0.get_mut(name.as_ref()) {
+            *count += 1;
+        } else if self.0.len() < MAX_NAMES {
+            self.0.insert(name.into(), 1);
+        } else {
+            *self.0.entry(OTHER.to_owned()).or_default() += 1;
+        }
+    }
+}
+
+fn serve(server: TcpListener, stats: &'static Stats) {
+    for stream in server.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                emit_err(&err, "SYSLOG stats request failed");
+                continue;
+            }
+        };
+
+        if CONNECTIONS.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+            CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+            emit("Too many SYSLOG stats requests; closing connection");
+            continue;
+        }
+
+        thread::spawn(move || {
+            if let Err(err) = respond(stream, stats) {
+                emit_err(&err, "SYSLOG stats request failed");
+            }
+
+            CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+fn respond(stream: TcpStream, stats: &Stats) -> Result<(), io::Error> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut request = BufReader::new((&stream).take(MAX_REQUEST_SIZE));
+
+    let mut request_line = String::new();
+    request.read_line(&mut request_line)?;
+
+    // Read the rest of the request so closing the connection doesn't reset it
+    let mut header = String::new();
+    while request.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    (&stream).write_all(&response(&request_line, stats))
+}
+
+fn response(request_line: &str, stats: &Stats) -> Vec<u8> {
+    let mut parts = request_line.split_whitespace();
+
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/stats")) => (
+            "200 OK",
+            serde_json::to_string(&stats.snapshot()).expect("failed to serialize stats"),
+        ),
+        _ => ("404 Not Found", r#"{"error":"not found, use `GET /stats`"}"#.to_owned()),
+    };
+
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stats_are_counted() {
+        let stats = Stats::new();
+
+        for (hostname, app) in [("web-1", "nginx"), ("web-2", "nginx"), ("web-1", "sshd")] {
+            let evt: clef::Message = serde_json::from_value(json!({
+                "@t": "2020-01-01T00:00:00Z",
+                "@l": "info",
+                "hostname": hostname,
+                "app_name": app,
+            }))
+            .expect("invalid event");
+
+            stats.event(Format::Rfc5424, &evt);
+        }
+
+        let evt: clef::Message = serde_json::from_value(json!({
+            "@t": "2020-01-01T00:00:00Z",
+            "@l": "info",
+        }))
+        .expect("invalid event");
+        stats.event(Format::Rfc3164, &evt);
+
+        stats.dropped(reason(&Error::Header {
+            offset: 4,
+            msg: "unexpected end of input".into(),
+        }));
+        stats.dropped(reason(&Error::Header {
+            offset: 7,
+            msg: "unexpected end of input".into(),
+        }));
+
+        let snapshot = stats.snapshot();
+
+        assert_eq!(2, snapshot.dropped);
+        assert_eq!(
+            vec![("RFC 3164", 1), ("RFC 5424", 3)],
+            snapshot.formats.iter().map(|(name, count)| (&**name, *count)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("-", 1), ("web-1", 2), ("web-2", 1)],
+            snapshot.hostnames.iter().map(|(name, count)| (&**name, *count)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("invalid header: unexpected end of input", 2)],
+            snapshot.drop_reasons.iter().map(|(name, count)| (&**name, *count)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn names_are_limited() {
+        let mut counts = Counts::default();
+
+        for i in 0..(MAX_NAMES + 10) {
+            counts.add(format!("host-{}", i));
+        }
+        counts.add("host-0");
+
+        assert_eq!(MAX_NAMES + 1, counts.0.len());
+        assert_eq!(Some(&10), counts.0.get(OTHER));
+        assert_eq!(Some(&2), counts.0.get("host-0"));
+    }
+
+    #[test]
+    fn stats_are_served() {
+        let stats = Stats::new();
+        stats.received.fetch_add(3, Ordering::Relaxed);
+
+        let ok = String::from_utf8(response("GET /stats HTTP/1.1\r\n", &stats)).expect("invalid response");
+        let (head, body) = ok.split_once("\r\n\r\n").expect("missing body");
+
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())), "{}", head);
+
+        let snapshot: Snapshot = serde_json::from_str(body).expect("invalid snapshot");
+        assert_eq!(3, snapshot.received);
+
+        let not_found = String::from_utf8(response("GET / HTTP/1.1\r\n", &stats)).expect("invalid response");
+        assert!(not_found.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", not_found);
+    }
+
+    #[test]
+    fn slow_clients_dont_hold_up_others() {
+        let server = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let addr = server.local_addr().expect("missing address");
+
+        let stats: &'static Stats = Box::leak(Box::new(Stats::new()));
+        thread::spawn(move || serve(server, stats));
+
+        // Connect without ever sending a request
+        let _slow = TcpStream::connect(addr).expect("failed to connect");
+
+        let started = Instant::now();
+
+        let mut client = TcpStream::connect(addr).expect("failed to connect");
+        client.write_all(b"GET /stats HTTP/1.1\r\n\r\n").expect("failed to send request");
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).expect("failed to read response");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(started.elapsed() < REQUEST_TIMEOUT, "{:?}", started.elapsed());
+    }
+}